@@ -8,66 +8,297 @@ use amethyst::{
     renderer::{ SpriteRender, SpriteSheet,},
     };
 
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+
 use crate::build::{set_player_position,place_inhabitants};
-use crate::config::{ArrakisConfig};
+use crate::config::{ArrakisConfig, CompassMode, PhaseConfig};
 use crate::components::{*};
 
 
 /// move the player to a new position, and calculate if the cell is special (encounter a fountain, armourer, magician or gold)
-pub fn perform_move(zone: &mut Zone, transform: &mut Transform, player: &mut Player, config: &ArrakisConfig) {
+#[allow(clippy::too_many_arguments)]
+pub fn perform_move(zone: &mut Zone, transform: &mut Transform, player: &mut Player, effects: &mut StatusEffects, inventory: &mut Inventory, stats: &mut RunStats, run: &Run, config: &ArrakisConfig, log: &mut MessageLog) {
     set_player_position(zone, transform, config);
-    player.strength = if player.strength > 0 {
-        player.strength - 1
+    // the heat of the day and the ground make moves more or less tiring
+    let phase = day_phase(player.turn, config);
+    let mut ground = config.terrain.kind(zone.terrain_at(zone.cell)).cost;
+    if player.power == Some(ArcanePower::Sandwalker) {
+        ground = ground.min(1.0);
+    }
+    let mut heat = phase.map_or(1.0, |p| p.cost);
+    if effects.has(EffectKind::HeatResistance) {
+        heat = heat.min(1.0);
+    }
+    let haste = if effects.has(EffectKind::Haste) { config.effects.haste_cost } else { 1.0 };
+    let cost = player.fatigue + heat * ground * haste;
+    player.fatigue = cost.fract();
+    player.strength = player.strength.saturating_sub(cost as u32);
+    player.turn += 1;
+    if let Some(next) = day_phase(player.turn, config) {
+        if phase.map(|p| &p.name) != Some(&next.name) {
+            log.add(next.message.clone());
+        }
+    }
+    player.light = player.light.saturating_sub(1);
+    wear_shields(zone, log);
+    calculate_encounter(zone, player, effects, inventory, stats, run, config, log);
+}
+
+/// the phase of the day at the given turn, if the day has any
+pub fn day_phase(turn: u32, config: &ArrakisConfig) -> Option<&PhaseConfig> {
+    let length: u32 = config.day.phases.iter().map(|p| p.length).sum();
+    if length == 0 {
+        return None;
+    }
+    let mut t = turn % length;
+    for phase in config.day.phases.iter() {
+        if t < phase.length {
+            return Some(phase);
+        }
+        t -= phase.length;
+    }
+    None
+}
+
+/// the day number at the given turn, starting at 1
+pub fn day_number(turn: u32, config: &ArrakisConfig) -> u32 {
+    let length: u32 = config.day.phases.iter().map(|p| p.length).sum();
+    if length == 0 {
+        1
     } else {
-        0
-    };
-    calculate_encounter(zone, player, config);
+        turn / length + 1
+    }
 }
 
 /// calculate if the current cell is a special encounter and apply changes
-pub fn calculate_encounter(zone: &mut Zone, player: &mut Player, config: &ArrakisConfig) {
+#[allow(clippy::too_many_arguments)]
+pub fn calculate_encounter(zone: &mut Zone, player: &mut Player, effects: &mut StatusEffects, inventory: &mut Inventory, stats: &mut RunStats, run: &Run, config: &ArrakisConfig, log: &mut MessageLog) {
+    let mut rng = run_rng(run, zone.current, player.turn);
     let (x,y) = zone.cell;
     zone.current_type = get_cell_type(zone, &(x,y), config);
+    if zone.current_type != CellType::Empty {
+        zone.discovered.entry(zone.current).or_default().insert((x,y), zone.current_type);
+    }
     
     match zone.current_type {
         CellType::Gold => {
             player.gold += config.gold;
+            stats.collect(config.gold);
+            log.add(format!("You found {} gold", config.gold));
+            if rng.gen_bool(config.items.found_chance.max(0.0).min(1.0)) {
+                let found: Vec<Item> = [Item::WaterFlask, Item::MapScroll, Item::Charm].iter().copied()
+                    .filter(|i| inventory.count(*i) < config.items.max_carried).collect();
+                if let Some(&item) = found.choose(&mut rng) {
+                    inventory.add(item);
+                    log.add(format!("You also find a {}", item.name()));
+                }
+            }
         },
         CellType::Fountain => {
-            player.strength = player.strength.max(100);
+            player.strength = player.strength.max(config.actions.fountain_strength);
+            log.add("You drink from the fountain and feel strong again");
+            if config.effects.heat_resistance > 0 {
+                effects.add(EffectKind::HeatResistance, config.effects.heat_resistance, player.turn);
+                log.add(format!("The cool water keeps the heat away for {} moves", config.effects.heat_resistance));
+            }
         },
         CellType::Armourer if player.gold>=config.armourer.gold => {
             player.charisma += config.armourer.charisma;
             player.magic += config.armourer.magic;
             player.gold -= config.armourer.gold;
+            stats.spend(config.armourer.gold);
+            log.add(format!("The armourer sells you {} charisma and {} magic for {} gold",
+                config.armourer.charisma, config.armourer.magic, config.armourer.gold));
             },
+        CellType::Armourer => {
+            log.add(format!("The armourer wants {} gold, you have {}", config.armourer.gold, player.gold));
+        },
         CellType::Magician => {
             player.magic += config.magician.magic;
             player.gold += config.magician.gold;
+            stats.collect(config.magician.gold);
             player.strength += config.magician.strength;
             player.charisma += config.magician.charisma;
+            log.add(format!("The magician gives you {} magic, {} gold, {} strength and {} charisma",
+                config.magician.magic, config.magician.gold, config.magician.strength, config.magician.charisma));
+            effects.add(EffectKind::Haste, config.effects.haste, player.turn);
+            effects.add(EffectKind::Invisibility, config.effects.invisibility, player.turn);
+            if config.effects.haste > 0 || config.effects.invisibility > 0 {
+                log.add("The magician's spell makes you quick and unseen for a while");
+            }
+            if config.spells.magicians_teach {
+                let unknown: Vec<Spell> = Spell::ALL.iter().copied().filter(|s| !player.spells.contains(s)).collect();
+                if let Some(&spell) = unknown.choose(&mut rng) {
+                    player.spells.push(spell);
+                    log.add(format!("The magician teaches you the {} spell, B opens your spellbook", spell.name()));
+                }
+            }
         },
+        CellType::Oracle => consult_oracle(zone, player, stats, config, log),
         _ => {},
     };
-    
+
+    // merchants also have items for sale, only bought if the player asks
+    let wares = merchant_wares(zone.current_type, inventory, config);
+    if !wares.is_empty() {
+        log.add(format!("The {} also sells {}: T to buy", merchant_name(zone.current_type), wares_text(&wares)));
+    }
+}
+
+/// the items a merchant sells and their price, in the order of the keys buying them
+pub fn merchant_wares(cell_type: CellType, inventory: &Inventory, config: &ArrakisConfig) -> Vec<(Item, u32)> {
+    let items = &config.items;
+    match cell_type {
+        CellType::Armourer if config.vision.enabled && inventory.count(Item::Lantern) == 0 => vec![
+            (Item::WaterFlask, items.flask_gold),
+            (Item::Charm, items.charm_gold),
+            (Item::Lantern, config.vision.lantern_gold),
+        ],
+        CellType::Armourer => vec![(Item::WaterFlask, items.flask_gold), (Item::Charm, items.charm_gold)],
+        CellType::Magician => vec![(Item::MapScroll, items.scroll_gold)],
+        _ => vec![],
+    }
+}
+
+/// the merchant of a cell type, in the messages
+pub fn merchant_name(cell_type: CellType) -> &'static str {
+    match cell_type {
+        CellType::Magician => "magician",
+        _ => "armourer",
+    }
+}
+
+/// the wares of a merchant with the number buying them, like "1 water flask 10 gold, 2 charm 10 gold"
+pub fn wares_text(wares: &[(Item, u32)]) -> String {
+    wares
+        .iter()
+        .enumerate()
+        .map(|(i, (item, price))| format!("{} {} {} gold", i + 1, item.name(), price))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// buy the item with the given number from the merchant of the current cell, if the player can carry more and pay for it
+pub fn buy_item(zone: &Zone, number: usize, player: &mut Player, inventory: &mut Inventory, stats: &mut RunStats, config: &ArrakisConfig, log: &mut MessageLog) -> bool {
+    let merchant = merchant_name(zone.current_type);
+    let wares = merchant_wares(zone.current_type, inventory, config);
+    let (item, price) = match number.checked_sub(1).and_then(|i| wares.get(i)) {
+        Some(ware) => *ware,
+        None => {
+            log.add(format!("The {} has nothing for sale under {}", merchant, number));
+            return false;
+        }
+    };
+    if item != Item::Lantern && inventory.count(item) >= config.items.max_carried {
+        log.add(format!("You cannot carry more than {} of each item", config.items.max_carried));
+        return false;
+    }
+    if player.gold < price {
+        log.add(format!("The {} wants {} gold for a {}, you have {}", merchant, price, item.name(), player.gold));
+        return false;
+    }
+    player.gold -= price;
+    stats.spend(price);
+    inventory.add(item);
+    log.add(format!("The {} sells you a {} for {} gold", merchant, item.name(), price));
+    true
+}
+
+/// the oracle shows the way to the target zone and reveals the encounters of the zone, for a price
+fn consult_oracle(zone: &mut Zone, player: &mut Player, stats: &mut RunStats, config: &ArrakisConfig, log: &mut MessageLog) {
+    let unlock = config.compass == CompassMode::Oracle && !player.compass;
+    let reveal = !zone.revealed.contains(&zone.current);
+    let clue = !zone.target_known();
+    if !unlock && !reveal && !clue {
+        log.add("The oracle has nothing more to tell you");
+        return;
+    }
+    if player.gold < config.oracle.gold {
+        log.add(format!("The oracle wants {} gold, you have {}", config.oracle.gold, player.gold));
+        return;
+    }
+    player.gold -= config.oracle.gold;
+    stats.spend(config.oracle.gold);
+    if let Some(message) = learn_clue(zone, ClueSource::Oracle) {
+        log.add(message);
+    }
+    if unlock {
+        player.compass = true;
+        if zone.target_known() {
+            log.add(format!("The oracle shows you the way to zone {}", zone.target));
+        } else {
+            log.add("The oracle will show you the way once you know where the wizard lives");
+        }
+    }
+    if reveal {
+        reveal_encounters(zone, config);
+        log.add("The oracle reveals the fountains, merchants and gold of this zone");
+    }
+}
+
+/// mark all the fountains, merchants and gold cells of the zone as discovered
+pub fn reveal_encounters(zone: &mut Zone, config: &ArrakisConfig) {
+    let mut found = vec![];
+    for x in 0..config.arena.cell_count {
+        for y in 0..config.arena.cell_count {
+            // walls and shields are not streets
+            if zone.cells[x][y] == 0 || zone.cells[x][y] == 18 {
+                match get_cell_type(zone, &(x,y), config) {
+                    CellType::Empty | CellType::Oracle => {},
+                    cell_type => found.push(((x,y), cell_type)),
+                }
+            }
+        }
+    }
+    zone.discovered.entry(zone.current).or_default().extend(found);
+    zone.revealed.insert(zone.current);
+}
+
+/// the encounters a shield put down on the player's cell would make around it
+pub fn shield_preview(zone: &Zone, config: &ArrakisConfig) -> Vec<((usize,usize), CellType)> {
+    let (x,y) = zone.cell;
+    let mut shielded = zone.clone();
+    shielded.cells[x][y] = 1;
+    let mut preview = vec![];
+    for x1 in get_neighbours_range(x,config.arena.cell_count){
+        for y1 in get_neighbours_range(y,config.arena.cell_count){
+            if (x!=x1 || y!=y1) && zone.cells[x1][y1] == 0 {
+                let cell_type = get_cell_type(&shielded, &(x1,y1), config);
+                if cell_type != CellType::Empty && cell_type != get_cell_type(zone, &(x1,y1), config) {
+                    preview.push(((x1,y1), cell_type));
+                }
+            }
+        }
+    }
+    preview
 }
 
 /// get the encounter cell type
-pub fn get_cell_type(zone: &mut Zone, pos: &(usize,usize), config: &ArrakisConfig) -> CellType {
+pub fn get_cell_type(zone: &Zone, pos: &(usize,usize), config: &ArrakisConfig) -> CellType {
     let (x,y) = *pos;
+    let weights = &config.encounters;
     let mut sc = 0;
     for x1 in get_neighbours_range(x,config.arena.cell_count){
         for y1 in get_neighbours_range(y,config.arena.cell_count){
             if x!=x1 || y!=y1 {
-                sc += zone.cells[x1][y1];
+                sc += match zone.cells[x1][y1] {
+                    1 => weights.shield,
+                    2 => weights.wall,
+                    18 => weights.inhabitant,
+                    _ => 0,
+                };
+                sc += config.terrain.kind(zone.terrain_at((x1,y1))).weight;
             }
         }
     }
     match sc {
-        0 => CellType::Gold,
-        10 => CellType::Fountain,
-        12 => CellType::Armourer,
-        14 => CellType::Magician,
+        s if s == weights.gold => CellType::Gold,
+        s if s == weights.oracle => CellType::Oracle,
+        s if s == weights.fountain => CellType::Fountain,
+        s if s == weights.armourer => CellType::Armourer,
+        s if s == weights.magician => CellType::Magician,
         _ => CellType::Empty,
     }
 }
@@ -85,61 +316,171 @@ fn get_neighbours_range(x: usize, cell_count: usize) -> Vec<usize> {
     }
 }
 
-/// get the valid range of cells for power action (doesn't include middle cell)
-fn get_power_range(x: usize, cell_count: usize) -> Vec<usize> {
-    if x > 0 {
-        if x < cell_count-1 {
-            vec!(x-1, x+1)
-        } else {
-             vec!(x-1)
+/// cells hit by the power action with the direction they're hit from, farthest first
+/// so that inhabitants can be pushed into the cells cleared behind them
+fn power_cells(pos: (usize, usize), shape: PowerShape, direction: (i32, i32), config: &ArrakisConfig) -> Vec<((usize, usize), (i32, i32))> {
+    let directions = match shape {
+        PowerShape::Cross => vec![(1, 0), (-1, 0), (0, 1), (0, -1)],
+        _ => vec![direction],
+    };
+    let range = match shape {
+        PowerShape::Line => config.actions.power.range as i32,
+        _ => 1,
+    };
+    let count = config.arena.cell_count as i32;
+    let mut cells = vec![];
+    for (dx, dy) in directions {
+        for i in (1..=range).rev() {
+            let (x, y) = (pos.0 as i32 + dx * i, pos.1 as i32 + dy * i);
+            if x >= 0 && x < count && y >= 0 && y < count {
+                cells.push(((x as usize, y as usize), (dx, dy)));
+            }
         }
-    } else {
-        vec!(x + 1)
     }
+    cells
 }
 
-/// clear cells in power action
-pub fn power_clear(zone: &mut Zone, pos: (usize, usize), config: &ArrakisConfig){
-    for x in get_power_range(pos.0, config.arena.cell_count){
-        zone.cells[x][pos.1] = 0;
-    }
-    for y in get_power_range(pos.1, config.arena.cell_count){
-        zone.cells[pos.0][y] = 0;
+/// clear cells in power action: the walls leave rubble behind, inhabitants are pushed back if they can be,
+/// shields stay. Returns the number of inhabitants pushed
+pub fn power_clear(zone: &mut Zone, pos: (usize, usize), shape: PowerShape, direction: (i32, i32), config: &ArrakisConfig) -> usize {
+    let count = config.arena.cell_count as i32;
+    let mut pushed = 0;
+    for ((x, y), (dx, dy)) in power_cells(pos, shape, direction, config) {
+        match zone.cells[x][y] {
+            2 => {
+                zone.cells[x][y] = 0;
+                zone.terrain[x][y] = Terrain::Rubble;
+            },
+            18 => {
+                let (nx, ny) = (x as i32 + dx, y as i32 + dy);
+                if nx >= 0 && nx < count && ny >= 0 && ny < count && can_move_to(zone, nx as usize, ny as usize, config) {
+                    let to = set_inhabitant_cell(zone, &(x, y), (nx as usize, ny as usize), config);
+                    if let Some(inhabitant) = zone.inhabitants.iter_mut().find(|p| **p == (x, y)) {
+                        *inhabitant = to;
+                    }
+                    pushed += 1;
+                }
+            },
+            _ => {},
+        }
     }
-    
+    pushed
+}
+
+/// can the player or inhabitants walk on the ground of the given cell?
+pub fn is_passable(zone: &Zone, pos: (usize, usize), config: &ArrakisConfig) -> bool {
+    config.terrain.kind(zone.terrain_at(pos)).passable
 }
 
-/// move inhabitants
-pub fn move_inhabitants<'s>(zone: &mut Zone,inhabitants: &ReadStorage<'s,Inhabitant>,positions: &mut WriteStorage<'s,Transform>, config: &ArrakisConfig) {
+/// move inhabitants, wearing down the shields that block them; they wander when the player is invisible
+pub fn move_inhabitants<'s>(zone: &mut Zone,inhabitants: &ReadStorage<'s,Inhabitant>,positions: &mut WriteStorage<'s,Transform>, invisible: bool, rng: &mut StdRng, config: &ArrakisConfig, log: &mut MessageLog) {
     
-    zone.inhabitants = zone.inhabitants.clone().iter().map(|pos| move_inhabitant(zone,pos,config)).collect();
+    zone.inhabitants = zone.inhabitants.clone().iter().map(|pos| move_inhabitant(zone,pos,invisible,rng,config)).collect();
+    expire_shields(zone, log);
 
     place_inhabitants(zone, inhabitants, positions, config);
 }
 
-/// move an inhabitant from the given cell towards the player
-fn move_inhabitant(zone : &mut Zone, pos: &(usize, usize), config: &ArrakisConfig) -> (usize,usize) {
+/// move an inhabitant from the given cell towards the player, or anywhere if the player is invisible
+fn move_inhabitant(zone : &mut Zone, pos: &(usize, usize), invisible: bool, rng: &mut StdRng, config: &ArrakisConfig) -> (usize,usize) {
     let (xp,yp) = zone.cell;
     let (x,y) = *pos;
-    if x<=xp && y<=yp && x<config.arena.cell_count-1 && y<config.arena.cell_count-1 && can_move_to(zone,x+1,y+1) {
-        return set_inhabitant_cell(zone,pos,(x+1,y+1),config);
-    }
-    if x<=xp && y>=yp && x<config.arena.cell_count-1 && y>0 && can_move_to(zone,x+1,y-1) {
-        return set_inhabitant_cell(zone,pos,(x+1,y-1),config);
+    let last = config.arena.cell_count-1;
+    // the diagonal moves towards the player, in order of preference
+    let mut candidates = [
+        (((x<=xp && y<=yp) || invisible) && x<last && y<last, (x+1,y+1)),
+        (((x<=xp && y>=yp) || invisible) && x<last && y>0, (x+1,y.wrapping_sub(1))),
+        (((x>=xp && y>=yp) || invisible) && x>0 && y>0, (x.wrapping_sub(1),y.wrapping_sub(1))),
+        (((x>=xp && y<=yp) || invisible) && x>0 && y<last, (x.wrapping_sub(1),y+1)),
+    ];
+    if invisible {
+        candidates.shuffle(rng);
     }
-    if x>=xp && y>=yp && x>0 && y>0 && can_move_to(zone,x-1,y-1) {
-        return set_inhabitant_cell(zone,pos,(x-1,y-1),config);
+    let mut shielded = vec![];
+    for &(possible, (x1,y1)) in candidates.iter() {
+        if !possible {
+            continue;
+        }
+        if can_move_to(zone,x1,y1,config) {
+            return set_inhabitant_cell(zone,pos,(x1,y1),config);
+        }
+        if zone.cells[x1][y1] == 1 && !shielded.contains(&(x1,y1)) {
+            shielded.push((x1,y1));
+        }
     }
-    if x>=xp && y<=yp && x>0 && y<config.arena.cell_count-1 && can_move_to(zone,x-1,y+1) {
-        return set_inhabitant_cell(zone,pos,(x-1,y+1),config);
+    // the inhabitant stays: the shields in its way each count one block
+    for shield in zone.shields.iter_mut().filter(|s| shielded.contains(&s.cell)) {
+        shield.blocks = shield.blocks.saturating_sub(1);
     }
     *pos
 }
 
+/// the cell a blink in the given direction lands on: the farthest free cell in range, in the zone
+pub fn blink_target(zone: &Zone, direction: (i32, i32), config: &ArrakisConfig) -> Option<(usize, usize)> {
+    let count = config.arena.cell_count as i32;
+    let (dx, dy) = direction;
+    for i in (1..=config.spells.blink_range as i32).rev() {
+        let (x, y) = (zone.cell.0 as i32 + dx * i, zone.cell.1 as i32 + dy * i);
+        // the wizard can't be taken by surprise
+        let wizard = zone.current == zone.target && (x as usize, y as usize) == wizard_cell(config);
+        if x >= 0 && x < count && y >= 0 && y < count && !wizard
+            && zone.cells[x as usize][y as usize] < 2 && is_passable(zone, (x as usize, y as usize), config) {
+            return Some((x as usize, y as usize));
+        }
+    }
+    None
+}
+
+/// push the inhabitants close to the player one cell away, if they can go there.
+/// Returns the number of inhabitants pushed
+pub fn push_inhabitants(zone: &mut Zone, config: &ArrakisConfig) -> usize {
+    let count = config.arena.cell_count as i32;
+    let range = config.spells.push_range as i32;
+    let (xp, yp) = (zone.cell.0 as i32, zone.cell.1 as i32);
+    let distance = |&(x, y): &(usize, usize)| (x as i32 - xp).abs().max((y as i32 - yp).abs());
+    // farthest first, so they make room for the ones behind them
+    let mut close: Vec<(usize, usize)> = zone.inhabitants.iter().copied().filter(|p| distance(p) <= range).collect();
+    close.sort_by_key(|p| -distance(p));
+    let mut pushed = 0;
+    for (x, y) in close {
+        let (nx, ny) = (x as i32 + (x as i32 - xp).signum(), y as i32 + (y as i32 - yp).signum());
+        if nx >= 0 && nx < count && ny >= 0 && ny < count && can_move_to(zone, nx as usize, ny as usize, config) {
+            let to = set_inhabitant_cell(zone, &(x, y), (nx as usize, ny as usize), config);
+            if let Some(inhabitant) = zone.inhabitants.iter_mut().find(|p| **p == (x, y)) {
+                *inhabitant = to;
+            }
+            pushed += 1;
+        }
+    }
+    pushed
+}
+
+/// which way the wizard is, in zones: only roughly while the target is hidden
+pub fn sense_wizard(zone: &mut Zone) -> String {
+    let (dx, dy) = zone.target_direction();
+    if dx == 0 && dy == 0 {
+        return "You sense the wizard in this zone".to_string();
+    }
+    if !zone.target_known() {
+        let heading = heading_name(dx, dy);
+        zone.clues.heading = Some(heading.clone());
+        return format!("You sense the wizard somewhere to the {}", heading);
+    }
+    let mut axes = vec![];
+    if dx != 0 {
+        axes.push(format!("{} zones {}", dx.abs(), if dx > 0 { "east" } else { "west" }));
+    }
+    if dy != 0 {
+        axes.push(format!("{} zones {}", dy.abs(), if dy > 0 { "north" } else { "south" }));
+    }
+    format!("You sense the wizard {}", axes.join(" and "))
+}
+
 /// can an inhabitant move to the given zone
-fn can_move_to(zone: &Zone, x: usize, y: usize) ->bool{
+fn can_move_to(zone: &Zone, x: usize, y: usize, config: &ArrakisConfig) ->bool{
     let (xp,yp) = zone.cell;
-    (x,y)!=(xp,yp) && zone.cells[x][y] == 0 && (zone.current != zone.target || x!=10 || y!=10)
+    (x,y)!=(xp,yp) && zone.cells[x][y] == 0 && is_passable(zone, (x,y), config)
+        && (zone.current != zone.target || (x,y)!=wizard_cell(config))
 }
 
 /// move the inhabitant from one cell to another
@@ -150,28 +491,186 @@ fn set_inhabitant_cell(zone : &mut Zone, pos: &(usize, usize),new_pos:(usize,usi
 }
 
 /// add a shield to the given position
-pub fn add_shield<'s>(zone: &mut Zone, pos: (usize,usize), entities: &Entities<'s>, sprite_sheet: &Handle<SpriteSheet>, 
-    transforms: &mut WriteStorage<'s, Transform>, sprites: &mut WriteStorage<'s, SpriteRender>, shields: &mut WriteStorage<'s,Shield>,
-    config: &ArrakisConfig){
-    let mut transform = Transform::default();
-    transform.set_translation_xyz(
-        pos.0 as f32 * config.cell.width + config.cell.width * 0.5, 
-        pos.1 as f32 * config.cell.height + config.cell.height * 0.5, 
-        0.0);
-
-    let sprite_render = SpriteRender {
-        sprite_sheet: sprite_sheet.clone(),
-        sprite_number: 4, 
-    };
-  
-    let shield = entities.build_entity()
-        .with(transform, transforms)
-        .with(sprite_render,sprites)
-        .with(Shield{position:pos,}, shields)
-        .build();
-   
+pub fn add_shield(zone: &mut Zone, pos: (usize,usize), config: &ArrakisConfig){
     zone.cells[pos.0][pos.1] = 1;
-    zone.shields.push(shield.id());
+    zone.shields.push(Shield {
+        cell: pos,
+        turns: config.shields.duration,
+        blocks: config.shields.blocks,
+    });
+}
+
+/// remove the shield at the given position, if there is one
+pub fn remove_shield(zone: &mut Zone, pos: (usize,usize)) -> Option<Shield> {
+    let index = zone.shields.iter().position(|s| s.cell == pos)?;
+    zone.cells[pos.0][pos.1] = 0;
+    Some(zone.shields.remove(index))
+}
+
+/// give back some of the magic of a picked up shield, less as it wears out:
+/// fractions add up until they make a whole point, the magic given back is returned
+pub fn refund_shield(shield: &Shield, player: &mut Player, config: &ArrakisConfig) -> f32 {
+    let left = shield.turns as f32 / config.shields.duration.max(1) as f32;
+    let refund = config.actions.shield_magic as f32 * config.shields.refund * left;
+    let total = player.magic_fraction + refund;
+    player.magic += total as u32;
+    player.magic_fraction = total.fract();
+    refund
+}
+
+/// wear the shields down by one turn
+pub fn wear_shields(zone: &mut Zone, log: &mut MessageLog) {
+    for shield in zone.shields.iter_mut() {
+        shield.turns = shield.turns.saturating_sub(1);
+    }
+    expire_shields(zone, log);
+}
+
+/// remove the shields that are worn out
+fn expire_shields(zone: &mut Zone, log: &mut MessageLog) {
+    let (gone, kept): (Vec<Shield>, Vec<Shield>) = zone.shields.drain(..).partition(|s| s.turns == 0 || s.blocks == 0);
+    zone.shields = kept;
+    for shield in gone.iter() {
+        zone.cells[shield.cell.0][shield.cell.1] = 0;
+    }
+    if !gone.is_empty() {
+        log.add("A shield fades away");
+    }
+}
+
+/// how far the player can see, in cells
+pub fn vision_radius(player: &Player, inventory: &Inventory, config: &ArrakisConfig) -> f32 {
+    let mut radius = config.vision.radius;
+    if inventory.count(Item::Lantern) > 0 {
+        radius += config.vision.lantern_radius;
+    }
+    if player.power == Some(ArcanePower::Farsight) {
+        radius += config.vision.lantern_radius;
+    }
+    if player.light > 0 {
+        radius += config.vision.light_radius;
+    }
+    radius
+}
+
+/// the cells the player can see, indexed by x then y: close enough and not behind a wall
+pub fn visible_cells(zone: &Zone, radius: f32, config: &ArrakisConfig) -> Vec<Vec<bool>> {
+    let count = config.arena.cell_count;
+    let (px, py) = (zone.cell.0 as i32, zone.cell.1 as i32);
+    let mut visible = vec![vec![false; count]; count];
+    for x in 0..count {
+        for y in 0..count {
+            let (dx, dy) = (x as i32 - px, y as i32 - py);
+            if ((dx * dx + dy * dy) as f32).sqrt() > radius {
+                continue;
+            }
+            // walk the line from the player, the cells in between must not be walls
+            let steps = dx.abs().max(dy.abs());
+            visible[x][y] = (1..steps).all(|i| {
+                let cx = px as f32 + dx as f32 * i as f32 / steps as f32;
+                let cy = py as f32 + dy as f32 * i as f32 / steps as f32;
+                zone.cells[cx.round() as usize][cy.round() as usize] != 2
+            });
+        }
+    }
+    visible
+}
+
+/// the cell of the wizard in the target zone: the middle of the zone
+pub fn wizard_cell(config: &ArrakisConfig) -> (usize,usize) {
+    (config.arena.cell_count / 2, config.arena.cell_count / 2)
+}
+
+/// the rough way to go for the given zones to go, like "north-east"
+fn heading_name(dx: i32, dy: i32) -> String {
+    let vertical = match dy.signum() {
+        1 => "north",
+        -1 => "south",
+        _ => "",
+    };
+    let horizontal = match dx.signum() {
+        1 => "east",
+        -1 => "west",
+        _ => "",
+    };
+    match (vertical, horizontal) {
+        ("", h) => h.to_string(),
+        (v, "") => v.to_string(),
+        (v, h) => format!("{}-{}", v, h),
+    }
+}
+
+/// the random draws of a run: seeded in the daily challenge from the zone and the turn, so that everyone gets the same run
+pub fn run_rng(run: &Run, zone: i32, turn: u32) -> StdRng {
+    if run.mode == GameMode::Daily {
+        StdRng::seed_from_u64(run.seed ^ zone as u64 ^ ((turn as u64) << 32))
+    } else {
+        StdRng::from_entropy()
+    }
+}
+
+/// the zone a new run starts in: chosen from the seed in the daily challenge, so that everyone starts in the same place
+pub fn start_zone(run: &Run) -> i32 {
+    let n1 = if run.mode == GameMode::Daily {
+        // not the same draws as the target
+        StdRng::seed_from_u64(!run.seed).gen_range(0..100)
+    } else {
+        rand::thread_rng().gen_range(0..100)
+    };
+    (n1 + 50) * 10
+}
+
+/// a target zone chosen from the run seed some rows away from the start zone, for a hidden target or the daily challenge
+pub fn hidden_target(start: i32, run: &Run, config: &ArrakisConfig) -> i32 {
+    let mut rng = StdRng::seed_from_u64(run.seed);
+    let hidden = &config.hidden_target;
+    let min_rows = hidden.min_rows.max(1);
+    let rows = rng.gen_range(min_rows..=hidden.max_rows.max(min_rows)) + config.plus.target_rows * run.plus as i32;
+    let row = start.div_euclid(100) + if rng.gen_bool(0.5) { rows } else { -rows };
+    row * 100 + rng.gen_range(0..10) * 10
+}
+
+/// learn something about the hidden target, the message to log is None when there is nothing to learn
+pub fn learn_clue(zone: &mut Zone, source: ClueSource) -> Option<String> {
+    if zone.target_known() {
+        return None;
+    }
+    let (dx, dy) = zone.target_direction();
+    let clues = &mut zone.clues;
+    let message = match source {
+        // an oracle tells the column, a scroll the row, or the other one when it's already known
+        ClueSource::Oracle | ClueSource::MapScroll => {
+            if (source == ClueSource::Oracle && !clues.column) || clues.row {
+                clues.column = true;
+            } else {
+                clues.row = true;
+            }
+            let name = zone.target_name();
+            match source {
+                ClueSource::Oracle => format!("The oracle tells you the wizard lives in zone {}", name),
+                _ => format!("The map scroll marks the wizard's zone: {}", name),
+            }
+        }
+        ClueSource::Inhabitant => {
+            let heading = heading_name(dx, dy);
+            clues.heading = Some(heading.clone());
+            format!(
+                "A charmed inhabitant whispers that the wizard lives {} zones away, to the {}",
+                dx.abs() + dy.abs(),
+                heading
+            )
+        }
+    };
+    Some(message)
+}
+
+/// the next target in endless mode, some zones away from the current one in a random direction
+pub fn next_target(zone: &Zone, turn: u32, run: &Run, config: &ArrakisConfig) -> i32 {
+    let distance = config.endless.distance.max(1);
+    let mut rng = run_rng(run, zone.current, turn);
+    let dy = rng.gen_range(-distance..=distance);
+    let dx = (distance - dy.abs()) * if rng.gen_bool(0.5) { 1 } else { -1 };
+    zone.current + dy * 100 + dx * 10
 }
 
 /// do we need to add the wizard entity (target zone and wizard entity doesn't exist)
@@ -183,10 +682,11 @@ pub fn need_add_wizard(zone: &Zone) -> bool {
 pub fn add_wizard<'s>(zone: &mut Zone, entities: &Entities<'s>, sprite_sheet: &Handle<SpriteSheet>, 
     transforms: &mut WriteStorage<'s, Transform>, sprites: &mut WriteStorage<'s, SpriteRender>, config: &ArrakisConfig ){
         if need_add_wizard(zone) {
+            let (wx, wy) = wizard_cell(config);
             let mut transform = Transform::default();
             transform.set_translation_xyz(
-                10.0 as f32 * config.cell.width + config.cell.width * 0.5, 
-                10.0 as f32 * config.cell.height + config.cell.height * 0.5, 
+                wx as f32 * config.cell.width + config.cell.width * 0.5, 
+                wy as f32 * config.cell.height + config.cell.height * 0.5, 
                 0.0);
 
             let sprite_render = SpriteRender {
@@ -204,9 +704,229 @@ pub fn add_wizard<'s>(zone: &mut Zone, entities: &Entities<'s>, sprite_sheet: &H
         } 
 }
 
-/// clear all shields
-pub fn clear_shields<'s>(zone: &mut Zone, entities: &Entities<'s>) {
-    while let Some(id) = zone.shields.pop() {
-        entities.delete(entities.entity(id)).unwrap();
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    fn seeded(seed: u64) -> Run {
+        Run { seed, ..Run::default() }
+    }
+
+    #[test]
+    fn hidden_target_comes_from_the_seed() {
+        let config = ArrakisConfig::default();
+        for seed in 0..50 {
+            assert_eq!(hidden_target(550, &seeded(seed), &config), hidden_target(550, &seeded(seed), &config));
+        }
+        let targets: HashSet<i32> = (0..50).map(|seed| hidden_target(550, &seeded(seed), &config)).collect();
+        assert!(targets.len() > 1);
+    }
+
+    #[test]
+    fn hidden_target_is_some_rows_away() {
+        let config = ArrakisConfig::default();
+        let hidden = &config.hidden_target;
+        for seed in 0..200 {
+            let target = hidden_target(550, &seeded(seed), &config);
+            let rows = (target.div_euclid(100) - 5).abs();
+            assert!(rows >= hidden.min_rows && rows <= hidden.max_rows, "{} is {} rows away", target, rows);
+            assert_eq!(target.rem_euclid(10), 0);
+        }
+    }
+
+    #[test]
+    fn hidden_target_moves_away_with_new_game_plus() {
+        let config = ArrakisConfig::default();
+        let hidden = &config.hidden_target;
+        for seed in 0..200 {
+            let run = Run { plus: 2, ..seeded(seed) };
+            let rows = (hidden_target(550, &run, &config).div_euclid(100) - 5).abs() - 2 * config.plus.target_rows;
+            assert!(rows >= hidden.min_rows && rows <= hidden.max_rows);
+        }
+    }
+
+    #[test]
+    fn daily_draws_are_the_same_for_everyone() {
+        let run = Run::daily("2026-10-19".to_string(), 42, true);
+        assert_eq!(start_zone(&run), start_zone(&Run::daily("2026-10-19".to_string(), 42, false)));
+        let (mut first, mut again) = (run_rng(&run, 350, 12), run_rng(&run, 350, 12));
+        for _ in 0..8 {
+            assert_eq!(first.gen::<u32>(), again.gen::<u32>());
+        }
+        // another turn or zone draws differently
+        assert_ne!(run_rng(&run, 350, 12).gen::<u64>(), run_rng(&run, 350, 13).gen::<u64>());
+        assert_ne!(run_rng(&run, 350, 12).gen::<u64>(), run_rng(&run, 360, 12).gen::<u64>());
+    }
+
+    /// a zone where the player stands next to the given number of walls
+    fn walled_zone(walls: usize, config: &ArrakisConfig) -> Zone {
+        let mut zone = Zone::new(350, 350, config.arena.cell_count);
+        let (x, y) = zone.cell;
+        for (dx, dy) in [(0, 0), (0, 1), (0, 2), (1, 0), (1, 2), (2, 0), (2, 1), (2, 2)].iter().take(walls) {
+            zone.cells[x + dx - 1][y + dy - 1] = 2;
+        }
+        zone
+    }
+
+    fn visit(zone: &mut Zone, player: &mut Player, inventory: &mut Inventory, config: &ArrakisConfig) {
+        let mut effects = StatusEffects::default();
+        let mut stats = RunStats::default();
+        let mut log = MessageLog::new(10);
+        calculate_encounter(zone, player, &mut effects, inventory, &mut stats, &Run::default(), config, &mut log);
+    }
+
+    #[test]
+    fn armourer_visit_buys_no_items_unasked() {
+        let mut config = ArrakisConfig::default();
+        config.vision.enabled = true;
+        let mut zone = walled_zone(6, &config);
+        let mut player = Player::default();
+        let mut inventory = Inventory::default();
+        visit(&mut zone, &mut player, &mut inventory, &config);
+        assert_eq!(zone.current_type, CellType::Armourer);
+        // only the armourer's stat deal, no lantern, flask or charm
+        assert_eq!(player.gold, 100 - config.armourer.gold);
+        assert_eq!(player.charisma, 5 + config.armourer.charisma);
+        assert!(inventory.items.is_empty());
+    }
+
+    #[test]
+    fn visit_without_enough_gold_leaves_gold_and_items() {
+        let config = ArrakisConfig::default();
+        let mut zone = walled_zone(6, &config);
+        let mut player = Player { gold: config.armourer.gold - 1, ..Player::default() };
+        let mut inventory = Inventory::default();
+        visit(&mut zone, &mut player, &mut inventory, &config);
+        assert_eq!(player.gold, config.armourer.gold - 1);
+        assert!(inventory.items.is_empty());
+    }
+
+    #[test]
+    fn magician_visit_buys_no_scroll_unasked() {
+        let config = ArrakisConfig::default();
+        let mut zone = walled_zone(7, &config);
+        let mut player = Player::default();
+        let mut inventory = Inventory::default();
+        visit(&mut zone, &mut player, &mut inventory, &config);
+        assert_eq!(zone.current_type, CellType::Magician);
+        assert_eq!(player.gold, 100 + config.magician.gold);
+        assert!(inventory.items.is_empty());
+    }
+
+    #[test]
+    fn buy_item_only_when_asked_and_affordable() {
+        let mut config = ArrakisConfig::default();
+        config.vision.enabled = true;
+        let mut zone = walled_zone(6, &config);
+        zone.current_type = CellType::Armourer;
+        let mut player = Player { gold: config.items.charm_gold, ..Player::default() };
+        let mut inventory = Inventory::default();
+        let mut stats = RunStats::default();
+        let mut log = MessageLog::new(10);
+        // the lantern is the third ware, too dear
+        assert!(!buy_item(&zone, 3, &mut player, &mut inventory, &mut stats, &config, &mut log));
+        assert!(!buy_item(&zone, 4, &mut player, &mut inventory, &mut stats, &config, &mut log));
+        assert_eq!(player.gold, config.items.charm_gold);
+        assert!(inventory.items.is_empty());
+        assert!(buy_item(&zone, 2, &mut player, &mut inventory, &mut stats, &config, &mut log));
+        assert_eq!(player.gold, 0);
+        assert_eq!(inventory.count(Item::Charm), 1);
+        assert_eq!(stats.gold_spent, config.items.charm_gold);
+    }
+
+    #[test]
+    fn buy_item_stops_at_what_can_be_carried() {
+        let config = ArrakisConfig::default();
+        let mut zone = walled_zone(7, &config);
+        zone.current_type = CellType::Magician;
+        let mut player = Player::default();
+        let mut inventory = Inventory::default();
+        let mut stats = RunStats::default();
+        let mut log = MessageLog::new(10);
+        for _ in 0..config.items.max_carried {
+            assert!(buy_item(&zone, 1, &mut player, &mut inventory, &mut stats, &config, &mut log));
+        }
+        let gold = player.gold;
+        assert!(!buy_item(&zone, 1, &mut player, &mut inventory, &mut stats, &config, &mut log));
+        assert_eq!(player.gold, gold);
+        assert_eq!(inventory.count(Item::MapScroll), config.items.max_carried);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn shield_refund_adds_up_fractions() {
+        let config = ArrakisConfig::default();
+        let duration = config.shields.duration;
+        let refund = |turns: u32, player: &mut Player| {
+            refund_shield(&Shield { cell: (0, 0), turns, blocks: 1 }, player, &config)
+        };
+        // a fresh shield gives back half its magic: two of them make a point
+        let mut player = Player { magic: 0, ..Player::default() };
+        assert_eq!(refund(duration, &mut player), 0.5);
+        assert_eq!(player.magic, 0);
+        refund(duration, &mut player);
+        assert_eq!(player.magic, 1);
+        assert_eq!(player.magic_fraction, 0.0);
+        // half worn, a quarter each
+        let mut player = Player { magic: 0, ..Player::default() };
+        for _ in 0..3 {
+            refund(duration / 2, &mut player);
+        }
+        assert_eq!(player.magic, 0);
+        refund(duration / 2, &mut player);
+        assert_eq!(player.magic, 1);
+        // one turn worn still gives back almost half
+        let mut player = Player { magic: 0, ..Player::default() };
+        assert!(refund(duration - 1, &mut player) > 0.45);
+        refund(duration - 1, &mut player);
+        refund(duration - 1, &mut player);
+        assert_eq!(player.magic, 1);
+        // a worn out shield gives nothing back
+        let mut player = Player { magic: 0, ..Player::default() };
+        assert_eq!(refund(0, &mut player), 0.0);
+        assert_eq!(player.magic, 0);
+        assert_eq!(player.magic_fraction, 0.0);
+    }
+
+    /// a zone with an inhabitant two cells below the player, and shields at the given cells
+    fn shielded_zone(shields: &[(usize, usize)], config: &ArrakisConfig) -> Zone {
+        let mut zone = Zone::new(350, 360, config.arena.cell_count);
+        let (x, y) = zone.cell;
+        zone.cells[x][y - 2] = 18;
+        zone.inhabitants.push((x, y - 2));
+        for cell in shields {
+            add_shield(&mut zone, *cell, config);
+        }
+        zone
+    }
+
+    #[test]
+    fn shield_avoided_is_not_worn() {
+        let config = ArrakisConfig::default();
+        let (x, y) = Zone::new(350, 360, config.arena.cell_count).cell;
+        let mut zone = shielded_zone(&[(x + 1, y - 1)], &config);
+        let mut rng = StdRng::seed_from_u64(0);
+        let moved = move_inhabitant(&mut zone, &(x, y - 2), false, &mut rng, &config);
+        assert_eq!(moved, (x - 1, y - 1));
+        assert_eq!(zone.shields[0].blocks, config.shields.blocks);
+    }
+
+    #[test]
+    fn shields_blocking_an_inhabitant_wear_once() {
+        let config = ArrakisConfig::default();
+        let (x, y) = Zone::new(350, 360, config.arena.cell_count).cell;
+        let mut zone = shielded_zone(&[(x + 1, y - 1), (x - 1, y - 1)], &config);
+        let mut rng = StdRng::seed_from_u64(0);
+        let stayed = move_inhabitant(&mut zone, &(x, y - 2), false, &mut rng, &config);
+        assert_eq!(stayed, (x, y - 2));
+        assert!(zone.shields.iter().all(|s| s.blocks == config.shields.blocks - 1));
+    }
+
+    #[test]
+    fn oracles_appear_without_shields() {
+        let config = ArrakisConfig::default();
+        let zone = walled_zone(4, &config);
+        assert!(zone.shields.is_empty());
+        assert_eq!(get_cell_type(&zone, &zone.cell, &config), CellType::Oracle);
+    }
+}
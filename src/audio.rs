@@ -4,6 +4,8 @@ use amethyst::{
     ecs::{World, WorldExt},
 };
 
+use crate::config::Options;
+
 const WALL_SOUND: &str = "audio/wall.wav";
 const FOUNTAIN_SOUND: &str = "audio/fountain.wav";
 const ARMOURER_SOUND: &str = "audio/armourer.wav";
@@ -11,6 +13,7 @@ const MAGICIAN_SOUND: &str = "audio/magician.wav";
 const CHARISMA_SOUND: &str = "audio/charisma.wav";
 const MAGIC_SOUND: &str = "audio/magic.wav";
 const POWER_SOUND: &str = "audio/power.wav";
+const WARNING_SOUND: &str = "audio/warning.wav";
 
 /// different sounds
 pub struct Sounds {
@@ -21,6 +24,9 @@ pub struct Sounds {
     pub charisma_sfx: SourceHandle,
     pub magic_sfx: SourceHandle,
     pub power_sfx: SourceHandle,
+    pub warning_sfx: SourceHandle,
+    /// volume set in the options, 0.0 to mute
+    pub volume: f32,
 }
 
 /// load a wav audio track
@@ -30,6 +36,7 @@ fn load_wav_track(loader: &Loader, world: &World, file: &str) -> SourceHandle {
 
 /// Initialise audio in the world
 pub fn initialize_audio(world: &mut World) {
+     let volume = world.read_resource::<Options>().volume;
      let sound_effects = {
         let loader = world.read_resource::<Loader>();
 
@@ -41,7 +48,8 @@ pub fn initialize_audio(world: &mut World) {
             charisma_sfx: load_wav_track(&loader, &world, CHARISMA_SOUND),
             magic_sfx: load_wav_track(&loader, &world, MAGIC_SOUND),
             power_sfx: load_wav_track(&loader, &world, POWER_SOUND),
-
+            warning_sfx: load_wav_track(&loader, &world, WARNING_SOUND),
+            volume,
         };
 
         sound
@@ -59,8 +67,11 @@ pub fn play_wall_sound(sounds: &Sounds, storage: &AssetStorage<Source>, output:
 pub fn play_sound<'a>(sounds: &'a Sounds, storage: &AssetStorage<Source>, output: Option<&Output>,
     f: &dyn Fn(&'a Sounds) -> &'a SourceHandle) {
     if let Some(ref output) = output.as_ref() {
+        if sounds.volume <= 0.0 {
+            return;
+        }
         if let Some(sound) = storage.get(f(sounds)) {
-            output.play_once(sound, 1.0);
+            output.play_once(sound, sounds.volume);
         }
     }
 }
@@ -1,22 +1,27 @@
 //! Functions to build terrain and entities
 extern crate rand;
 
+use crate::arrakis::{add_wizard, hidden_target, is_passable, run_rng, start_zone, wizard_cell};
 use crate::components::*;
 use crate::config::ArrakisConfig;
+use crate::save::SaveGame;
 use amethyst::{
     assets::{AssetStorage, Handle, Loader},
-    core::transform::Transform,
-    ecs::{Entity, Join, ReadStorage, WriteStorage},
+    core::{
+        math::{Point3, Vector3},
+        transform::Transform,
+        Hidden,
+    },
+    ecs::{Entities, Entity, Join, ReadStorage, WriteStorage},
     prelude::*,
     renderer::{
-        palette::Srgba, resources::Tint, Camera, ImageFormat, SpriteRender, SpriteSheet,
-        SpriteSheetFormat, Texture,
+        palette::Srgba, resources::Tint, transparent::Transparent, Camera, ImageFormat, SpriteRender,
+        SpriteSheet, SpriteSheetFormat, Texture,
     },
-    ui::{Anchor, FontHandle, LineMode, TtfFormat, UiText, UiTransform},
-    window::ScreenDimensions,
+    tiles::TileMap,
+    ui::{Anchor, FontHandle, LineMode, TtfFormat, UiCreator, UiImage, UiText, UiTransform},
 };
 use rand::seq::SliceRandom;
-use rand::Rng;
 use std::ops::Deref;
 
 /// load the font for messages
@@ -26,23 +31,14 @@ pub fn load_font(world: &mut World) -> FontHandle {
         .load("font/square.ttf", TtfFormat, (), &world.read_resource())
 }
 
-/// initialize the camera
+/// initialize the camera, looking at the logical screen (the resize system fits it to the window)
 pub fn initialize_camera(world: &mut World) {
     let mut transform = Transform::default();
-    let (width, height) = /*{
-            let dim = world.read_resource::<ScreenDimensions>();
-            (dim.width(), dim.height())
-        }; */ (900.0,640.0);
-    println!(
-        "Screen dimensions on initialize_camera:{},{}",
-        width, height
-    );
+    let (width, height) = {
+        let config = world.read_resource::<ArrakisConfig>();
+        (config.screen.width, config.screen.height)
+    };
     transform.set_translation_xyz(width * 0.5, height * 0.5, 1.0);
-    /*{
-        let mut config = world.write_resource::<ArrakisConfig>();
-        config.cell.width *=  width / 900.0;
-        config.cell.height *=  height / 640.0;
-    }*/
 
     world
         .create_entity()
@@ -51,14 +47,56 @@ pub fn initialize_camera(world: &mut World) {
         .build();
 }
 
-/// create an entity for each cell to show walls
+/// build a UI transform and its logical layout, from the center of the element in logical screen coordinates
+pub fn scaled_ui(
+    id: &str,
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+    font_size: f32,
+) -> (UiTransform, ScaledUi) {
+    let transform = UiTransform::new(
+        id.to_string(),
+        Anchor::Middle,
+        Anchor::Middle,
+        0.0,
+        0.0,
+        1.,
+        width,
+        height,
+    );
+    (
+        transform,
+        ScaledUi {
+            x,
+            y,
+            width,
+            height,
+            font_size,
+        },
+    )
+}
+
+/// create the tile map showing the zone terrain
 pub fn initialize_terrain(world: &mut World, sprite_sheet: &Handle<SpriteSheet>) {
     let config = world.read_resource::<ArrakisConfig>().deref().clone();
-    for x in 0..20 {
-        for y in 0..20 {
-            build_wall(x, y, world, sprite_sheet.clone(), &config);
-        }
-    }
+    let count = config.arena.cell_count as u32;
+    let map = TileMap::<TerrainTile>::new(
+        Vector3::new(count, count, 1),
+        Vector3::new(config.cell.width as u32, config.cell.height as u32, 1),
+        Some(sprite_sheet.clone()),
+    );
+
+    // the map is centered on its transform, behind the sprites
+    let mut transform = Transform::default();
+    transform.set_translation_xyz(
+        count as f32 * config.cell.width * 0.5,
+        count as f32 * config.cell.height * 0.5,
+        -1.0,
+    );
+
+    world.create_entity().with(map).with(transform).build();
 }
 
 /// create inhabitant entities
@@ -68,7 +106,8 @@ fn initialize_inhabitants(world: &mut World, sprite_sheet: Handle<SpriteSheet>)
         sprite_number: 1,
     };
     let config = world.read_resource::<ArrakisConfig>().deref().clone();
-    for _ in 0..config.inhabitants {
+    let count = config.inhabitant_count(&world.read_resource::<Run>());
+    for _ in 0..count {
         world
             .create_entity()
             .with(Inhabitant::default())
@@ -78,8 +117,12 @@ fn initialize_inhabitants(world: &mut World, sprite_sheet: Handle<SpriteSheet>)
     }
 }
 
-/// create player entity and inhabitants of initial zone
-pub fn initialize_player(world: &mut World, sprite_sheet: Handle<SpriteSheet>, font: FontHandle) {
+/// create player entity and inhabitants of initial zone, or of the saved game if given
+pub fn initialize_player(
+    world: &mut World,
+    sprite_sheet: Handle<SpriteSheet>,
+    save: Option<SaveGame>,
+) {
     initialize_inhabitants(world, sprite_sheet.clone());
 
     let sprite_render = SpriteRender {
@@ -87,26 +130,49 @@ pub fn initialize_player(world: &mut World, sprite_sheet: Handle<SpriteSheet>, f
         sprite_number: 2,
     };
     let config = world.read_resource::<ArrakisConfig>().deref().clone();
-    let mut rng = rand::thread_rng();
-
-    let n1 = rng.gen_range(0..100);
-    let mut zone = Zone {
-        current: (n1 + 50) * 10,
-        target: 350,
-        cells: [[0; 20]; 20],
-        cell: (config.arena.cell_count / 2, config.arena.cell_count / 2),
-        current_type: CellType::Empty,
-        inhabitants: vec![],
-        shields: vec![],
-        wizard: None,
+    let run = world.read_resource::<Run>().deref().clone();
+
+    let (player, mut zone, effects, inventory, stats) = match save {
+        Some(save) => (save.player, save.zone, save.effects, save.inventory, save.stats),
+        None => {
+            let mut zone = Zone::new(
+                start_zone(&run),
+                // each New Game+ puts the target farther down
+                350 - 100 * config.plus.target_rows * run.plus as i32,
+                config.arena.cell_count,
+            );
+            if config.hidden_target.enabled || run.mode == GameMode::Daily {
+                zone.target = hidden_target(zone.current, &run, &config);
+                zone.clues.hidden = config.hidden_target.enabled;
+            }
+            build_zone(&mut zone, &config, &run);
+            let player = Player {
+                charisma: config.player.charisma,
+                gold: config.player.gold,
+                magic: config.player.magic,
+                strength: config.player.strength,
+                compass: false,
+                light: 0,
+                turn: 0,
+                fatigue: 0.0,
+                magic_fraction: 0.0,
+                spells: if run.power == Some(ArcanePower::Spellmaster) {
+                    Spell::ALL.to_vec()
+                } else {
+                    config.spells.start.clone()
+                },
+                power: run.power,
+                action: None,
+            };
+            (player, zone, StatusEffects::default(), Inventory::default(), RunStats::default())
+        }
     };
-
-    build_zone(&mut zone, &config);
+    zone.visited.insert(zone.current);
+    restore_zone_entities(world, &mut zone, &sprite_sheet, &config);
 
     {
-        let cells = world.read_storage::<Cell>();
-        let mut tints = world.write_storage::<Tint>();
-        show_walls(&zone, &cells, &mut tints);
+        let mut tilemaps = world.write_storage::<TileMap<TerrainTile>>();
+        show_terrain(&zone, &mut tilemaps, &config);
     }
     let mut transform = Transform::default();
     set_player_position(&zone, &mut transform, &config);
@@ -119,72 +185,39 @@ pub fn initialize_player(world: &mut World, sprite_sheet: Handle<SpriteSheet>, f
 
     world
         .create_entity()
-        .with(Player {
-            charisma: config.player.charisma,
-            gold: config.player.gold,
-            magic: config.player.magic,
-            strength: config.player.strength,
-            action: None,
-            current_state: CurrentState::Gameplay,
-        })
+        .with(player)
         .with(zone)
+        .with(effects)
+        .with(inventory)
+        .with(stats)
         .with(sprite_render)
         .with(transform)
         .build();
+}
 
-    let values_transform = UiTransform::new(
-        String::from("StatusValues"),
-        Anchor::TopRight,
-        Anchor::TopLeft,
-        -config.status.values_width,
-        0.,
-        1.,
-        config.status.values_width,
-        config.status.height * 0.5,
-    );
-
-    let values_uit = UiText::new(
-        font.clone(),
-        String::new(),
-        [1., 1., 1., 1.],
-        config.status.font_size,
-        LineMode::Wrap,
-        Anchor::TopRight,
-    );
-
-    world
-        .create_entity()
-        .with(values_transform)
-        .with(values_uit)
-        .with(Status)
-        .build();
-
-    let encounter_transform = UiTransform::new(
-        String::from("EncounterValue"),
-        Anchor::TopRight,
-        Anchor::TopMiddle,
-        -config.status.width,
-        -config.status.height * 0.5,
-        1.,
-        config.status.width,
-        config.status.height * 0.5,
-    );
-
-    let encounter_uit = UiText::new(
-        font.clone(),
-        String::new(),
-        [1., 1., 1., 1.],
-        config.status.font_size,
-        LineMode::Single,
-        Anchor::TopRight,
+/// create the wizard entity if we're in the target zone (needed when loading a game)
+fn restore_zone_entities(
+    world: &mut World,
+    zone: &mut Zone,
+    sprite_sheet: &Handle<SpriteSheet>,
+    config: &ArrakisConfig,
+) {
+    world.exec(
+        |(entities, mut transforms, mut sprites): (
+            Entities,
+            WriteStorage<Transform>,
+            WriteStorage<SpriteRender>,
+        )| {
+            add_wizard(
+                zone,
+                &entities,
+                sprite_sheet,
+                &mut transforms,
+                &mut sprites,
+                config,
+            );
+        },
     );
-
-    world
-        .create_entity()
-        .with(encounter_transform)
-        .with(encounter_uit)
-        .with(Encounter)
-        .build();
 }
 
 /// set player position via transform
@@ -218,42 +251,230 @@ pub fn load_sprite_sheet(world: &mut World) -> Handle<SpriteSheet> {
     )
 }
 
-/// create text for player's stats names
-pub fn initialize_text(world: &mut World, font: FontHandle) {
-    let (names_transform, names_uit) = {
-        let status = &world.read_resource::<ArrakisConfig>().status;
-        let names_transform = UiTransform::new(
-            "StatusNames".to_string(),
-            Anchor::TopRight,
-            Anchor::TopLeft,
-            -status.width,
-            0.,
-            1.,
-            status.text_width,
-            status.height * 0.5,
-        );
-        let names = format!(
-            "{}\n{}\n{}\n{}\n{}\n{}",
-            "Strength", "Magic", "Charisma", "Gold", "Current Zone", "Target Zone",
+/// create the HUD from its prefab, laid out on the right of the zone
+pub fn initialize_hud(world: &mut World) {
+    let (x, y, width, height, font_size) = {
+        let config = world.read_resource::<ArrakisConfig>();
+        let status = &config.status;
+        (
+            config.screen.width - status.width * 0.5,
+            config.screen.height * 0.5,
+            status.width,
+            status.height,
+            config.hud.font_size,
+        )
+    };
+    let hud = world.exec(|mut creator: UiCreator<'_>| creator.create("ui/hud.ron", ()));
+    let (_, scaled) = scaled_ui("hud", x, y, width, height, font_size);
+    world.write_storage::<ScaledUi>().insert(hud, scaled).unwrap();
+}
+
+/// create the color laid over the zone for the time of day
+pub fn initialize_day_tint(world: &mut World) {
+    let (mut transform, scaled) = {
+        let config = world.read_resource::<ArrakisConfig>();
+        scaled_ui(
+            "day_tint",
+            config.arena.width * 0.5,
+            config.screen.height * 0.5,
+            config.arena.width,
+            config.arena.height,
+            config.status.font_size,
+        )
+    };
+    // under the other UI elements over the zone
+    transform.local_z = 0.5;
+
+    world
+        .create_entity()
+        .with(transform)
+        .with(scaled)
+        .with(UiImage::SolidColor([0., 0., 0., 0.]))
+        .build();
+}
+
+/// create the sprites previewing the encounters a shield would make, hidden until the magic key is held
+pub fn initialize_shield_preview(world: &mut World, sprite_sheet: &Handle<SpriteSheet>) {
+    // a shield changes the encounters of its eight neighbours
+    for _ in 0..8 {
+        world
+            .create_entity()
+            .with(SpriteRender {
+                sprite_sheet: sprite_sheet.clone(),
+                sprite_number: 5,
+            })
+            .with(Transform::default())
+            .with(Tint(Srgba::new(1.0, 1.0, 1.0, 0.6)))
+            .with(Transparent)
+            .with(ShieldPreview)
+            .with(Hidden)
+            .build();
+    }
+}
+
+/// create the full message history, over the zone and hidden until the log is expanded
+pub fn initialize_log(world: &mut World, font: FontHandle) {
+    let (transform, scaled, font_size) = {
+        let config = world.read_resource::<ArrakisConfig>();
+        let (transform, scaled) = scaled_ui(
+            "log_history",
+            config.arena.width * 0.5,
+            config.screen.height * 0.5,
+            config.arena.width,
+            config.arena.height,
+            config.log.font_size,
         );
+        (transform, scaled, config.log.font_size)
+    };
 
-        let names_uit = UiText::new(
-            font.clone(),
-            names.to_string(),
-            [1., 1., 1., 1.],
-            status.font_size,
-            LineMode::Wrap,
-            Anchor::TopLeft,
+    let text = UiText::new(
+        font,
+        String::new(),
+        [1., 1., 1., 1.],
+        font_size,
+        LineMode::Wrap,
+        Anchor::TopLeft,
+    );
+
+    world
+        .create_entity()
+        .with(transform)
+        .with(scaled)
+        .with(text)
+        .with(UiImage::SolidColor([0., 0., 0., 0.85]))
+        .with(LogHistory)
+        .with(Hidden)
+        .build();
+}
+
+/// create the spellbook panel over the arena, hidden until opened
+pub fn initialize_spellbook(world: &mut World, font: FontHandle) {
+    let (transform, scaled, font_size) = {
+        let config = world.read_resource::<ArrakisConfig>();
+        let (transform, scaled) = scaled_ui(
+            "spellbook",
+            config.arena.width * 0.5,
+            config.screen.height * 0.5,
+            config.arena.width * 0.6,
+            config.arena.height * 0.5,
+            config.spells.font_size,
         );
-        (names_transform, names_uit)
+        (transform, scaled, config.spells.font_size)
     };
 
+    let text = UiText::new(
+        font,
+        String::new(),
+        [1., 0.95, 0.8, 1.],
+        font_size,
+        LineMode::Wrap,
+        Anchor::TopLeft,
+    );
+
     world
         .create_entity()
-        .with(names_transform)
-        .with(names_uit)
+        .with(transform)
+        .with(scaled)
+        .with(text)
+        .with(UiImage::SolidColor([0.1, 0.05, 0.15, 0.9]))
+        .with(SpellbookPanel)
+        .with(Hidden)
+        .build();
+}
+
+/// create the minimap in the HUD, and the world map over the whole screen, hidden until asked for
+pub fn initialize_map(world: &mut World, font: FontHandle) {
+    let config = world.read_resource::<ArrakisConfig>().deref().clone();
+    let map = &config.map;
+    let cell = map.cell_size;
+    // the minimap sits in the HUD, between the zone names and the log
+    let center_x = config.screen.width - config.status.width * 0.5;
+    let center_y = config.screen.height - config.status.height * 0.55 - (map.radius as f32 + 0.5) * cell;
+    for x in -map.radius..=map.radius {
+        for y in -map.radius..=map.radius {
+            let (transform, scaled) = scaled_ui(
+                &format!("minimap_{}_{}", x, y),
+                center_x + x as f32 * cell,
+                center_y + y as f32 * cell,
+                cell - 2.0,
+                cell - 2.0,
+                map.font_size,
+            );
+            world
+                .create_entity()
+                .with(transform)
+                .with(scaled)
+                .with(map_text(&font, map.font_size))
+                .with(UiImage::SolidColor([0., 0., 0., 0.]))
+                .with(MapCell { x, y, world: false })
+                .build();
+        }
+    }
+
+    // the world is ten zones wide
+    let width = config.screen.width;
+    let height = config.screen.height;
+    let cell_width = (width - 100.0) / 10.0;
+    let cell_height = (height - 90.0) / map.world_rows as f32;
+    let title_size = map.world_font_size * 1.5;
+    let (mut transform, scaled) = scaled_ui("world_map", width * 0.5, height * 0.5, width, height, title_size);
+    transform.local_z = 3.;
+    let title = UiText::new(
+        font.clone(),
+        "World map (Tab to close)".to_string(),
+        [1., 1., 1., 1.],
+        title_size,
+        LineMode::Single,
+        Anchor::TopMiddle,
+    );
+    world
+        .create_entity()
+        .with(transform)
+        .with(scaled)
+        .with(title)
+        .with(UiImage::SolidColor([0., 0., 0., 0.9]))
+        .with(WorldMap)
+        .with(Hidden)
         .build();
+    let top = height - 60.0;
+    for x in 0..10 {
+        for row in 0..map.world_rows {
+            let y = map.world_rows / 2 - row;
+            let (mut transform, scaled) = scaled_ui(
+                &format!("world_map_{}_{}", x, y),
+                width * 0.5 + (x as f32 - 4.5) * cell_width,
+                top - (row as f32 + 0.5) * cell_height,
+                cell_width - 2.0,
+                cell_height - 2.0,
+                map.world_font_size,
+            );
+            transform.local_z = 4.;
+            world
+                .create_entity()
+                .with(transform)
+                .with(scaled)
+                .with(map_text(&font, map.world_font_size))
+                .with(UiImage::SolidColor([0., 0., 0., 0.]))
+                .with(MapCell { x, y, world: true })
+                .with(WorldMap)
+                .with(Hidden)
+                .build();
+        }
+    }
+}
+
+/// the text of a map cell
+fn map_text(font: &FontHandle, font_size: f32) -> UiText {
+    UiText::new(
+        font.clone(),
+        String::new(),
+        [1., 1., 1., 1.],
+        font_size,
+        LineMode::Wrap,
+        Anchor::Middle,
+    )
 }
+
 /*
 pub fn is_next_to_inhabitant(zone: &Zone, config: &ArrakisConfig) -> bool {
     let (x,y) = zone.cell;
@@ -271,34 +492,46 @@ pub fn is_next_to_inhabitant(zone: &Zone, config: &ArrakisConfig) -> bool {
 }*/
 
 /// build a given zone
-pub fn build_zone(zone: &mut Zone, config: &ArrakisConfig) {
+pub fn build_zone(zone: &mut Zone, config: &ArrakisConfig, run: &Run) {
+    let count = config.arena.cell_count;
     let mut n = 0;
 
-    for i in zone.current..zone.current + 80 {
+    zone.cells = vec![vec![0; count]; count];
+    zone.shields.clear();
+    // each zone number gives 5 cells
+    for i in zone.current..zone.current + ((count * count + 4) / 5) as i32 {
         // use sinus of zone number to generate walls
         let mut sin = ((i as f32).sin().abs() * 100000.0) as i32;
         for _ in 0..5 {
+            if n == count * count {
+                break;
+            }
             let c = sin % 10;
             sin /= 10;
-            let y = n / config.arena.cell_count;
-            let x = n - (y * config.arena.cell_count);
+            let y = n / count;
+            let x = n - (y * count);
             n += 1;
-            let wall = c < config.arena.wall_threshold && !(x == zone.cell.0 && y == zone.cell.1);
+            let wall = c < config.wall_threshold(run) && !(x == zone.cell.0 && y == zone.cell.1);
             zone.cells[x][y] = if wall { 2 } else { 0 };
         }
     }
+    build_terrain(zone, config);
     // ensure we can reach wizard
+    let (wx, wy) = wizard_cell(config);
     if zone.current == zone.target {
-        zone.cells[10][10] = 0;
+        zone.cells[wx][wy] = 0;
+        if !is_passable(zone, (wx, wy), config) {
+            zone.terrain[wx][wy] = Terrain::Street;
+        }
     }
 
     // put inhabitants in empty spaces
     let mut empties = vec![];
     for x in 0..config.arena.cell_count {
         for y in 0..config.arena.cell_count {
-            if zone.cells[x][y] == 0 {
+            if zone.cells[x][y] == 0 && is_passable(zone, (x, y), config) {
                 if x != zone.cell.0 || y != zone.cell.1 {
-                    if zone.current != zone.target || x != 10 || y != 10 {
+                    if zone.current != zone.target || x != wx || y != wy {
                         empties.push((x, y));
                     }
                 }
@@ -306,12 +539,44 @@ pub fn build_zone(zone: &mut Zone, config: &ArrakisConfig) {
         }
     }
     zone.inhabitants.clear();
-    for (x, y) in empties.choose_multiple(&mut rand::thread_rng(), config.inhabitants) {
+    // the daily challenge has the same inhabitants for everyone
+    let mut rng = run_rng(run, zone.current, 0);
+    for (x, y) in empties.choose_multiple(&mut rng, config.inhabitant_count(run)) {
         zone.cells[*x][*y] = 18;
         zone.inhabitants.push((*x, *y));
     }
 }
 
+/// build the ground of the zone, in patches of 2x2 cells
+fn build_terrain(zone: &mut Zone, config: &ArrakisConfig) {
+    let count = config.arena.cell_count;
+    zone.terrain = vec![vec![Terrain::Street; count]; count];
+    let kinds = [Terrain::Street, Terrain::Sand, Terrain::Shade, Terrain::Rubble];
+    let total: u32 = kinds.iter().map(|k| config.terrain.kind(*k).frequency).sum();
+    if total == 0 {
+        return;
+    }
+    for x in 0..count {
+        for y in 0..count {
+            // use sinus of zone number like the walls, so a zone always has the same ground
+            let seed = zone.current as f32 * 97.0 + (x / 2) as f32 * 13.0 + (y / 2) as f32 * 7.0;
+            let mut pick = ((seed.sin().abs() * 100000.0) as u32) % total;
+            for kind in kinds.iter() {
+                let frequency = config.terrain.kind(*kind).frequency;
+                if pick < frequency {
+                    zone.terrain[x][y] = *kind;
+                    break;
+                }
+                pick -= frequency;
+            }
+        }
+    }
+    // the player must be able to stand where they arrive
+    if !is_passable(zone, zone.cell, config) {
+        zone.terrain[zone.cell.0][zone.cell.1] = Terrain::Street;
+    }
+}
+
 /// place inhabitants transform
 pub fn place_inhabitants<'s>(
     zone: &Zone,
@@ -328,50 +593,56 @@ pub fn place_inhabitants<'s>(
     }
 }
 
-/// show walls by modifying tints
-pub fn show_walls<'s>(
-    zone: &Zone,
-    cells: &ReadStorage<'s, Cell>,
-    tints: &mut WriteStorage<'s, Tint>,
-) {
-    for (cell, tint) in (cells, tints).join() {
-        if zone.cells[cell.position.0][cell.position.1] == 2 {
-            tint.0.alpha = 1.0;
-        } else {
-            tint.0.alpha = 0.0;
-        }
+/// sprite showing a cell: walls, shields, discovered encounters if configured, and the ground
+fn cell_sprite(zone: &Zone, pos: (usize, usize), config: &ArrakisConfig) -> usize {
+    let discovered = if config.reveal_encounters {
+        zone.discovered_at(pos).and_then(encounter_sprite)
+    } else {
+        None
+    };
+    match (zone.cells[pos.0][pos.1], discovered) {
+        (1, _) => 4,
+        (2, _) => 0,
+        (_, Some(sprite)) => sprite,
+        _ => match zone.terrain_at(pos) {
+            Terrain::Street => 5,
+            Terrain::Sand => 11,
+            Terrain::Shade => 12,
+            Terrain::Rubble => 13,
+        },
     }
 }
 
-/// build a wall entity
-fn build_wall(
-    x: usize,
-    y: usize,
-    world: &mut World,
-    sprite_sheet: Handle<SpriteSheet>,
+/// sprite showing an encounter
+pub fn encounter_sprite(cell_type: CellType) -> Option<usize> {
+    match cell_type {
+        CellType::Fountain => Some(6),
+        CellType::Armourer => Some(7),
+        CellType::Magician => Some(8),
+        CellType::Gold => Some(9),
+        CellType::Oracle => Some(10),
+        CellType::Empty => None,
+    }
+}
+
+/// show the zone by updating the terrain tiles
+pub fn show_terrain<'s>(
+    zone: &Zone,
+    tilemaps: &mut WriteStorage<'s, TileMap<TerrainTile>>,
     config: &ArrakisConfig,
 ) {
-    let mut transform = Transform::default();
-    transform.set_translation_xyz(
-        x as f32 * config.cell.width + config.cell.width * 0.5,
-        y as f32 * config.cell.height + config.cell.height * 0.5,
-        0.0,
-    );
-
-    let sprite_render = SpriteRender {
-        sprite_sheet: sprite_sheet,
-        sprite_number: 0,
-    };
-
-    let tint = Tint(Srgba::new(1.0, 1.0, 1.0, 0.0));
-
-    world
-        .create_entity()
-        .with(Cell { position: (x, y) })
-        .with(tint)
-        .with(sprite_render)
-        .with(transform)
-        .build();
+    for tilemap in tilemaps.join() {
+        let count = zone.cells.len();
+        for x in 0..count {
+            for y in 0..count {
+                // tile rows go down from the top
+                let coord = Point3::new(x as u32, (count - 1 - y) as u32, 0);
+                if let Some(tile) = tilemap.get_mut(&coord) {
+                    tile.sprite = Some(cell_sprite(zone, (x, y), config));
+                }
+            }
+        }
+    }
 }
 
 /// build the text for message screens
@@ -383,20 +654,13 @@ pub fn initialize_inter_text(
     anchor: &Anchor,
     font_ratio: f32,
 ) -> Entity {
-    let (width, height) = {
-        let dim = world.read_resource::<ScreenDimensions>();
-        (dim.width(), dim.height())
-    }; //(900.0,640.0);
-
-    let names_transform = UiTransform::new(
-        "EndMessage".to_string(),
-        Anchor::Middle,
-        Anchor::Middle,
-        0.0,
-        0.0,
-        1.,
-        width,
-        height,
+    let (names_transform, names_scaled) = scaled_ui(
+        "EndMessage",
+        config.screen.width * 0.5,
+        config.screen.height * 0.5,
+        config.screen.width,
+        config.screen.height,
+        config.status.font_size * font_ratio,
     );
 
     let names_uit = UiText::new(
@@ -411,6 +675,7 @@ pub fn initialize_inter_text(
     world
         .create_entity()
         .with(names_transform)
+        .with(names_scaled)
         .with(names_uit)
         .build()
 }
@@ -1,35 +1,66 @@
 //! Components and useful data structures
 use amethyst::{
-    ecs::prelude::{Component, DenseVecStorage, NullStorage},
+    core::math::{Point3, Vector3},
+    ecs::prelude::{Component, DenseVecStorage, FlaggedStorage, NullStorage, World},
     ecs::world::Index,
+    renderer::palette::Srgba,
+    tiles::Tile,
 };
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::f32::consts::PI;
 
 /// Actions that have a non immediate effect
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
 pub enum Action {
-    Charisma,
-    //Magic,
-    //Power,
     Restart,
     Help,
 }
 
-/// state between gameplay and help screen
+/// state between gameplay and menus or message screens, stored as a resource
+/// so that gameplay systems ignore input while another state is pushed
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum CurrentState {
     Intertext,
     Gameplay,
 }
 
+impl Default for CurrentState {
+    fn default() -> Self {
+        CurrentState::Intertext
+    }
+}
+
 /// player stats
-#[derive(Debug)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Player {
     pub strength: u32,
     pub magic: u32,
     pub charisma: u32,
     pub gold: u32,
+    /// has the compass been unlocked by an oracle?
+    #[serde(default)]
+    pub compass: bool,
+    /// moves left with a magic light
+    #[serde(default)]
+    pub light: u32,
+    /// moves since the start of the game, for the time of day
+    #[serde(default)]
+    pub turn: u32,
+    /// fraction of strength lost but not taken yet
+    #[serde(default)]
+    pub fatigue: f32,
+    /// fraction of magic given back but not counted yet
+    #[serde(default)]
+    pub magic_fraction: f32,
+    /// spells learned, in the order of the spellbook
+    #[serde(default)]
+    pub spells: Vec<Spell>,
+    /// arcane power taught by the wizard
+    #[serde(default)]
+    pub power: Option<ArcanePower>,
+    #[serde(skip)]
     pub action: Option<Action>,
-    pub current_state: CurrentState,
 }
 
 impl Default for Player {
@@ -39,8 +70,14 @@ impl Default for Player {
             magic: 5,
             charisma: 5,
             gold: 100,
+            compass: false,
+            light: 0,
+            turn: 0,
+            fatigue: 0.0,
+            magic_fraction: 0.0,
+            spells: vec![],
+            power: None,
             action: None,
-            current_state: CurrentState::Gameplay,
         }
     }
 
@@ -51,81 +88,934 @@ impl Component for Player {
 }
 
 /// Empty cell type, with special encounters 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Deserialize, Serialize)]
 pub enum CellType {
     Empty,
     Fountain,
     Armourer,
     Magician,
     Gold,
+    Oracle,
+}
+
+/// Kind of ground of a cell, under walls, shields and inhabitants
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Deserialize, Serialize)]
+pub enum Terrain {
+    Street,
+    Sand,
+    Shade,
+    Rubble,
+}
+
+impl Default for Terrain {
+    fn default() -> Self {
+        Terrain::Street
+    }
+}
+
+impl Terrain {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Terrain::Street => "Street",
+            Terrain::Sand => "Sand",
+            Terrain::Shade => "Shade",
+            Terrain::Rubble => "Rubble",
+        }
+    }
+}
+
+/// A magic shield put down in the zone, until it's worn out
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Shield {
+    pub cell: (usize,usize),
+    /// turns left
+    pub turns: u32,
+    /// inhabitant moves it can still block
+    pub blocks: u32,
 }
 
 /// the zone
-#[derive(Debug)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Zone {
     /// current zone number
     pub current: i32,
     /// target zone
     pub target: i32,
-    /// status status
-    pub cells: [[i32; 20]; 20],
+    /// status status, indexed by x then y
+    pub cells: Vec<Vec<i32>>,
+    /// ground of each cell, indexed by x then y
+    pub terrain: Vec<Vec<Terrain>>,
     /// current cell
     pub cell: (usize,usize),
     /// current cell type
     pub current_type: CellType,
     /// inhabitants cell position
     pub inhabitants: Vec<(usize,usize)>,
-    /// shield entities ID
-    pub shields: Vec<Index>,
     /// target wizard entity ID
+    #[serde(skip)]
     pub wizard: Option<Index>,
+    /// encounter cells discovered in each zone
+    #[serde(default)]
+    pub discovered: HashMap<i32, HashMap<(usize,usize), CellType>>,
+    /// zones whose encounters have been revealed by an oracle
+    #[serde(default)]
+    pub revealed: HashSet<i32>,
+    /// zones the player has entered
+    #[serde(default)]
+    pub visited: HashSet<i32>,
+    /// shields put down in the zone
+    #[serde(default)]
+    pub shields: Vec<Shield>,
+    /// what the player knows of the target, when it is hidden
+    #[serde(default)]
+    pub clues: TargetClues,
 }
 
 impl Component for Zone {
     type Storage = DenseVecStorage<Self>;
 }
 
-/// Mark status text
+impl Zone {
+    /// a zone of empty cells, with the player in the middle and nothing discovered yet
+    pub fn new(current: i32, target: i32, cell_count: usize) -> Zone {
+        Zone {
+            current,
+            target,
+            cells: vec![vec![0; cell_count]; cell_count],
+            terrain: vec![vec![Terrain::Street; cell_count]; cell_count],
+            cell: (cell_count / 2, cell_count / 2),
+            current_type: CellType::Empty,
+            inhabitants: vec![],
+            wizard: None,
+            discovered: HashMap::new(),
+            revealed: HashSet::new(),
+            visited: HashSet::new(),
+            shields: vec![],
+            clues: TargetClues::default(),
+        }
+    }
+
+    /// the encounter discovered at the given cell of the current zone, if any
+    pub fn discovered_at(&self, pos: (usize,usize)) -> Option<CellType> {
+        self.discovered.get(&self.current).and_then(|cells| cells.get(&pos)).copied()
+    }
+
+    /// the ground at the given cell
+    pub fn terrain_at(&self, pos: (usize,usize)) -> Terrain {
+        self.terrain[pos.0][pos.1]
+    }
+
+    /// does the player know the target zone
+    pub fn target_known(&self) -> bool {
+        !self.clues.hidden || (self.clues.row && self.clues.column)
+    }
+
+    /// the target zone as far as the player knows, with ? for what is still hidden
+    pub fn target_name(&self) -> String {
+        if self.target_known() {
+            return self.target.to_string();
+        }
+        match (self.clues.row, self.clues.column) {
+            (true, _) => format!("{}?0", self.target.div_euclid(100)),
+            (_, true) => format!("?{}0", self.target.rem_euclid(100) / 10),
+            _ => "???".to_string(),
+        }
+    }
+
+    /// zones to go to reach the target, horizontally (right is positive) then vertically (up is positive)
+    pub fn target_direction(&self) -> (i32, i32) {
+        // a zone to the right is 10 more, a zone up is 100 more: take the shortest way
+        let steps = (self.target - self.current) / 10;
+        let mut dy = steps.div_euclid(10);
+        let mut dx = steps.rem_euclid(10);
+        if dx > 5 || (dx == 5 && dy < 0) {
+            dx -= 10;
+            dy += 1;
+        }
+        (dx, dy)
+    }
+}
+
+/// What the player has learned of a hidden target
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct TargetClues {
+    /// is the target hidden at all
+    pub hidden: bool,
+    /// the row of zones of the target is known
+    pub row: bool,
+    /// the column of zones of the target is known
+    pub column: bool,
+    /// rough way to the target from where the last hint was given, like "north-east"
+    pub heading: Option<String>,
+}
+
+/// Where a clue about the hidden target comes from
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ClueSource {
+    /// tells the column of the target
+    Oracle,
+    /// tells the row of the target
+    MapScroll,
+    /// tells the way and the distance
+    Inhabitant,
+}
+
+/// Terrain tile in the zone tile map, updated from the zone cells
+#[derive(Clone, Debug, Default)]
+pub struct TerrainTile {
+    /// sprite to draw
+    pub sprite: Option<usize>,
+    /// out of sight of the player
+    pub dim: bool,
+}
+
+impl Tile for TerrainTile {
+    fn sprite(&self, _: Point3<u32>, _: &World) -> Option<usize> {
+        self.sprite
+    }
+
+    fn tint(&self, _: Point3<u32>, _: &World) -> Srgba {
+        if self.dim {
+            Srgba::new(0.25, 0.25, 0.3, 1.0)
+        } else {
+            Srgba::new(1.0, 1.0, 1.0, 1.0)
+        }
+    }
+}
+
+/// Inhabitant marker component
 #[derive(Default)]
-pub struct Status;
+pub struct Inhabitant {
+}
 
-impl Component for Status {
+impl Component for Inhabitant {
     type Storage = NullStorage<Self>;
 }
 
-/// Mark Encounter text
+/// UI element laid out in logical screen coordinates (origin bottom left, like sprites),
+/// fitted to the window by the resize system
+#[derive(Debug, Clone, Copy)]
+pub struct ScaledUi {
+    /// center of the element
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    pub font_size: f32,
+}
+
+impl Component for ScaledUi {
+    // flagged, so that the resize system can fit the new elements only
+    type Storage = FlaggedStorage<Self, DenseVecStorage<Self>>;
+}
+
+/// Ratio between window pixels and logical screen units, set by the resize system
+#[derive(Debug, Clone, Copy)]
+pub struct ScreenScale(pub f32);
+
+impl Default for ScreenScale {
+    fn default() -> Self {
+        ScreenScale(1.0)
+    }
+}
+
+/// Kinds of animation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TweenKind {
+    /// go from one position to the other
+    Move,
+    /// go a bit towards the other position and come back
+    Bump,
+}
+
+/// Animate the translation of an entity over a short time
+#[derive(Debug, Clone)]
+pub struct Tween {
+    pub kind: TweenKind,
+    pub from: Vector3<f32>,
+    pub to: Vector3<f32>,
+    /// duration in seconds
+    pub duration: f32,
+    pub elapsed: f32,
+}
+
+impl Tween {
+    pub fn new(kind: TweenKind, from: Vector3<f32>, to: Vector3<f32>, duration: f32) -> Self {
+        Tween {
+            kind,
+            from,
+            to,
+            duration,
+            elapsed: 0.0,
+        }
+    }
+
+    /// is the animation over?
+    pub fn is_done(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+
+    /// current position
+    pub fn position(&self) -> Vector3<f32> {
+        let t = if self.duration > 0.0 {
+            (self.elapsed / self.duration).min(1.0)
+        } else {
+            1.0
+        };
+        match self.kind {
+            // ease out
+            TweenKind::Move => self.from + (self.to - self.from) * (t * (2.0 - t)),
+            TweenKind::Bump => self.from + (self.to - self.from) * ((PI * t).sin() * 0.3),
+        }
+    }
+}
+
+impl Component for Tween {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// moves are the actions that are animated
+const MOVES: [&str; 4] = ["up", "down", "left", "right"];
+
+/// Actions pressed during gameplay, waiting for the current animation to finish
+#[derive(Debug, Default)]
+pub struct ActionQueue {
+    pub actions: VecDeque<String>,
+}
+
+impl ActionQueue {
+    /// take the next action if it's a move
+    pub fn pop_move(&mut self) -> Option<String> {
+        match self.actions.front() {
+            Some(action) if MOVES.contains(&action.as_str()) => self.actions.pop_front(),
+            _ => None,
+        }
+    }
+
+    /// take the next action if it's not a move
+    pub fn pop_other(&mut self) -> Option<String> {
+        match self.actions.front() {
+            Some(action) if !MOVES.contains(&action.as_str()) => self.actions.pop_front(),
+            _ => None,
+        }
+    }
+}
+
+/// Messages about game events, shown in the log panel
+#[derive(Debug, Default)]
+pub struct MessageLog {
+    /// oldest first
+    pub messages: VecDeque<String>,
+    /// is the full history shown?
+    pub expanded: bool,
+    /// how many messages to keep
+    pub capacity: usize,
+}
+
+impl MessageLog {
+    pub fn new(capacity: usize) -> Self {
+        MessageLog {
+            messages: VecDeque::new(),
+            expanded: false,
+            capacity,
+        }
+    }
+
+    /// add a message, forgetting the oldest ones if needed
+    pub fn add<S: Into<String>>(&mut self, message: S) {
+        self.messages.push_back(message.into());
+        while self.messages.len() > self.capacity {
+            self.messages.pop_front();
+        }
+    }
+
+    /// the given number of latest messages, newest last
+    pub fn latest(&self, count: usize) -> Vec<&str> {
+        let skip = self.messages.len().saturating_sub(count);
+        self.messages.iter().skip(skip).map(|m| m.as_str()).collect()
+    }
+}
+
+/// Items carried in the inventory
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Deserialize, Serialize)]
+pub enum Item {
+    /// gives some strength back
+    WaterFlask,
+    /// reveals the fountains, merchants and gold of the zone
+    MapScroll,
+    /// keeps the inhabitants still, without using charisma
+    Charm,
+    /// see further when vision is limited, never used up
+    Lantern,
+}
+
+impl Item {
+    /// name in the messages
+    pub fn name(self) -> &'static str {
+        match self {
+            Item::WaterFlask => "water flask",
+            Item::MapScroll => "map scroll",
+            Item::Charm => "charm",
+            Item::Lantern => "lantern",
+        }
+    }
+
+    /// name in the HUD, with the key using the item
+    pub fn label(self) -> &'static str {
+        match self {
+            Item::WaterFlask => "1 Flask",
+            Item::MapScroll => "2 Scroll",
+            Item::Charm => "3 Charm",
+            Item::Lantern => "Lantern",
+        }
+    }
+}
+
+/// Inventory component, the items carried by the player
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Inventory {
+    pub items: BTreeMap<Item, u32>,
+}
+
+impl Inventory {
+    /// how many of the given item are carried
+    pub fn count(&self, item: Item) -> u32 {
+        self.items.get(&item).copied().unwrap_or(0)
+    }
+
+    pub fn add(&mut self, item: Item) {
+        *self.items.entry(item).or_insert(0) += 1;
+    }
+
+    /// use up one of the given item, if there's any
+    pub fn take(&mut self, item: Item) -> bool {
+        match self.items.get_mut(&item) {
+            Some(count) if *count > 0 => {
+                *count -= 1;
+                if *count == 0 {
+                    self.items.remove(&item);
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Component for Inventory {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// Statistics of the current run, shown on the end screen and added to the lifetime stats
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct RunStats {
+    pub moves: u32,
+    pub zones: u32,
+    pub gold_collected: u32,
+    pub gold_spent: u32,
+    /// special cells stepped on, by type
+    pub encounters: BTreeMap<CellType, u32>,
+    pub shields: u32,
+    pub powers: u32,
+    pub bumps: u32,
+    /// seconds spent playing, menus aside
+    pub play_time: f32,
+}
+
+impl RunStats {
+    /// count the encounter of the current cell, if it's special
+    pub fn encounter(&mut self, cell_type: CellType) {
+        if cell_type != CellType::Empty {
+            *self.encounters.entry(cell_type).or_insert(0) += 1;
+        }
+    }
+
+    /// count gold given to the player
+    pub fn collect(&mut self, gold: u32) {
+        self.gold_collected += gold;
+    }
+
+    /// count gold paid by the player
+    pub fn spend(&mut self, gold: u32) {
+        self.gold_spent += gold;
+    }
+
+    /// add the stats of another run
+    pub fn add(&mut self, other: &RunStats) {
+        self.moves += other.moves;
+        self.zones += other.zones;
+        self.gold_collected += other.gold_collected;
+        self.gold_spent += other.gold_spent;
+        for (cell_type, count) in other.encounters.iter() {
+            *self.encounters.entry(*cell_type).or_insert(0) += count;
+        }
+        self.shields += other.shields;
+        self.powers += other.powers;
+        self.bumps += other.bumps;
+        self.play_time += other.play_time;
+    }
+
+    /// a few lines for the end screen
+    pub fn summary(&self) -> String {
+        let seconds = self.play_time as u32;
+        let encounters = self
+            .encounters
+            .iter()
+            .map(|(cell_type, count)| format!("{:?} {}", cell_type, count))
+            .collect::<Vec<_>>();
+        format!(
+            "Moves {}, zones visited {}, play time {}:{:02}\nGold collected {}, spent {}\nEncounters: {}\nShields {}, powers {}, wall bumps {}",
+            self.moves,
+            self.zones,
+            seconds / 60,
+            seconds % 60,
+            self.gold_collected,
+            self.gold_spent,
+            if encounters.is_empty() { "none".to_string() } else { encounters.join(", ") },
+            self.shields,
+            self.powers,
+            self.bumps
+        )
+    }
+}
+
+impl Component for RunStats {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// Kinds of timed effects on the player
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Deserialize, Serialize)]
+pub enum EffectKind {
+    /// the inhabitants don't move
+    Frozen,
+    /// moves are less tiring
+    Haste,
+    /// the heat of the day doesn't make moves more tiring
+    HeatResistance,
+    /// the inhabitants don't see you and wander around
+    Invisibility,
+}
+
+impl EffectKind {
+    /// name shown in the HUD
+    pub fn name(self) -> &'static str {
+        match self {
+            EffectKind::Frozen => "Charm",
+            EffectKind::Haste => "Haste",
+            EffectKind::HeatResistance => "Cool",
+            EffectKind::Invisibility => "Unseen",
+        }
+    }
+}
+
+/// a timed effect
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+pub struct StatusEffect {
+    pub kind: EffectKind,
+    /// moves left
+    pub turns: u32,
+    /// player turn the moves left are counted from
+    pub since: u32,
+}
+
+/// Status effect component, the timed effects on the player
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct StatusEffects {
+    pub effects: Vec<StatusEffect>,
+}
+
+impl StatusEffects {
+    /// is the given effect active?
+    pub fn has(&self, kind: EffectKind) -> bool {
+        self.effects.iter().any(|e| e.kind == kind && e.turns > 0)
+    }
+
+    /// start an effect at the given turn, or make it last longer if it's already active
+    pub fn add(&mut self, kind: EffectKind, turns: u32, turn: u32) {
+        if turns == 0 {
+            return;
+        }
+        match self.effects.iter_mut().find(|e| e.kind == kind) {
+            Some(effect) => {
+                // the moves since the last count may not have been taken off yet
+                effect.turns = effect.turns.saturating_sub(turn.saturating_sub(effect.since)).max(turns);
+                effect.since = turn;
+            }
+            None => self.effects.push(StatusEffect { kind, turns, since: turn }),
+        }
+    }
+
+    /// count down the effects to the given turn, returning the ones that wore off
+    pub fn tick(&mut self, turn: u32) -> Vec<EffectKind> {
+        for effect in self.effects.iter_mut() {
+            let elapsed = turn.saturating_sub(effect.since);
+            effect.turns = effect.turns.saturating_sub(elapsed);
+            effect.since = effect.since.max(turn);
+        }
+        let expired = self.effects.iter().filter(|e| e.turns == 0).map(|e| e.kind).collect();
+        self.effects.retain(|e| e.turns > 0);
+        expired
+    }
+}
+
+impl Component for StatusEffects {
+    type Storage = DenseVecStorage<Self>;
+}
+
+/// Shield preview marker component, for the sprites showing the encounters a shield would make
 #[derive(Default)]
-pub struct Encounter;
+pub struct ShieldPreview {
+}
 
-impl Component for Encounter {
+impl Component for ShieldPreview {
     type Storage = NullStorage<Self>;
 }
 
-/// Cell component
+/// Full message history marker component
 #[derive(Default)]
-pub struct Cell{
-    pub position: (usize,usize),
+pub struct LogHistory {
 }
 
-impl Component for Cell {
-    type Storage = DenseVecStorage<Self>;
+impl Component for LogHistory {
+    type Storage = NullStorage<Self>;
 }
 
-/// Inhabitant marker component
+/// Arcane powers the wizard teaches at the end of the game
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Deserialize, Serialize)]
+pub enum ArcanePower {
+    /// sand and rubble are no more tiring than the streets
+    Sandwalker,
+    /// the power action costs no gold
+    Stormcaller,
+    /// always know the way to the target, and see further
+    Farsight,
+    /// know all the spells
+    Spellmaster,
+}
+
+impl ArcanePower {
+    pub const ALL: [ArcanePower; 4] = [
+        ArcanePower::Sandwalker,
+        ArcanePower::Stormcaller,
+        ArcanePower::Farsight,
+        ArcanePower::Spellmaster,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ArcanePower::Sandwalker => "Sandwalker",
+            ArcanePower::Stormcaller => "Stormcaller",
+            ArcanePower::Farsight => "Farsight",
+            ArcanePower::Spellmaster => "Spellmaster",
+        }
+    }
+
+    /// what the power does, as the wizard tells it
+    pub fn description(self) -> &'static str {
+        match self {
+            ArcanePower::Sandwalker => "the sand and the rubble will not tire you more than the streets",
+            ArcanePower::Stormcaller => "your power will clear walls without costing you gold",
+            ArcanePower::Farsight => "you will always know the way, and see further in the dark",
+            ArcanePower::Spellmaster => "you will know every spell there is",
+        }
+    }
+}
+
+/// Game modes
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Deserialize, Serialize)]
+pub enum GameMode {
+    /// find the wizard, pay him and learn a power
+    Classic,
+    /// the wizard moves on each time you reach him, until you die
+    Endless,
+    /// the same world for everyone on a given day
+    Daily,
+}
+
+impl Default for GameMode {
+    fn default() -> Self {
+        GameMode::Classic
+    }
+}
+
+impl GameMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            GameMode::Classic => "Classic",
+            GameMode::Endless => "Endless",
+            GameMode::Daily => "Daily",
+        }
+    }
+}
+
+/// The current run, stored as a resource and kept in saved games
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Run {
+    pub mode: GameMode,
+    /// New Game+ level: each one makes the zones harder and the target farther
+    pub plus: u32,
+    /// arcane power learned from the wizard in the previous run
+    pub power: Option<ArcanePower>,
+    /// targets reached in endless mode
+    pub cleared: u32,
+    /// seed the hidden target and the inhabitants are chosen from, picked when the run starts
+    pub seed: u64,
+    /// the day of the daily challenge
+    pub date: String,
+    /// is this the first attempt of the daily challenge, the one that counts
+    pub ranked: bool,
+}
+
+impl Run {
+    /// an endless run
+    pub fn endless() -> Run {
+        Run {
+            mode: GameMode::Endless,
+            ..Run::default()
+        }
+    }
+
+    /// the daily challenge of the given day, from its seed
+    pub fn daily(date: String, seed: u64, ranked: bool) -> Run {
+        Run {
+            mode: GameMode::Daily,
+            seed,
+            date,
+            ranked,
+            ..Run::default()
+        }
+    }
+
+    /// the same kind of run, from the start: the daily challenge keeps its world but no longer counts
+    pub fn restart(&self) -> Run {
+        Run {
+            cleared: 0,
+            seed: if self.mode == GameMode::Daily { self.seed } else { 0 },
+            ranked: false,
+            ..self.clone()
+        }
+    }
+
+    /// the next run, harder, keeping the power learned from the wizard
+    pub fn new_game_plus(&self, power: ArcanePower) -> Run {
+        Run {
+            mode: GameMode::Classic,
+            plus: self.plus + 1,
+            power: Some(power),
+            ..Run::default()
+        }
+    }
+}
+
+/// Spells that can be learned and cast from the spellbook
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Deserialize, Serialize)]
+pub enum Spell {
+    /// jump a few cells in a direction, over walls and inhabitants
+    Blink,
+    /// get some strength back
+    Heal,
+    /// show the fountains, merchants and gold of the zone
+    Reveal,
+    /// push the inhabitants around away
+    Push,
+    /// feel which way the wizard is
+    Sense,
+}
+
+impl Spell {
+    /// all the spells, in the order they're taught
+    pub const ALL: [Spell; 5] = [Spell::Sense, Spell::Heal, Spell::Reveal, Spell::Push, Spell::Blink];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Spell::Blink => "Blink",
+            Spell::Heal => "Heal",
+            Spell::Reveal => "Reveal",
+            Spell::Push => "Push",
+            Spell::Sense => "Sense the wizard",
+        }
+    }
+}
+
+/// Is the spellbook open, and which spell is selected?
+#[derive(Debug, Default)]
+pub struct Spellbook {
+    pub open: bool,
+    /// index in the player spells
+    pub selected: usize,
+    /// blink cast, waiting for the player to choose a direction
+    pub blinking: bool,
+}
+
+/// Spellbook panel marker component
 #[derive(Default)]
-pub struct Inhabitant {
+pub struct SpellbookPanel {
 }
 
-impl Component for Inhabitant {
+impl Component for SpellbookPanel {
     type Storage = NullStorage<Self>;
 }
 
-/// Shield position component
-#[derive(Default)]
-pub struct Shield{
-    pub position: (usize,usize),
+/// Is the full world map shown?
+#[derive(Debug, Default)]
+pub struct MapView {
+    pub expanded: bool,
+}
+
+/// A cell of the minimap or of the world map, showing one zone
+#[derive(Debug, Clone, Copy)]
+pub struct MapCell {
+    /// zones right of the current zone on the minimap, column of the world map
+    pub x: i32,
+    /// zones up from the current zone on the minimap, rows up from the current row on the world map
+    pub y: i32,
+    /// part of the world map rather than the minimap
+    pub world: bool,
+}
+
+impl MapCell {
+    /// the zone shown in this cell, when the player is in the given zone
+    pub fn zone(&self, current: i32) -> i32 {
+        if self.world {
+            current.div_euclid(100) * 100 + self.y * 100 + self.x * 10
+        } else {
+            current + self.x * 10 + self.y * 100
+        }
+    }
 }
 
-impl Component for Shield {
+impl Component for MapCell {
     type Storage = DenseVecStorage<Self>;
 }
+
+/// World map marker component, for everything hidden with the world map
+#[derive(Default)]
+pub struct WorldMap {
+}
+
+impl Component for WorldMap {
+    type Storage = NullStorage<Self>;
+}
+
+/// Shapes of the power action
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerShape {
+    /// cells in a line from the player
+    Line,
+    /// the next cell
+    Single,
+    /// the four cells around the player
+    Cross,
+}
+
+impl PowerShape {
+    /// the next shape to choose, none after the last one to give up
+    pub fn next(self) -> Option<PowerShape> {
+        match self {
+            PowerShape::Line => Some(PowerShape::Single),
+            PowerShape::Single => Some(PowerShape::Cross),
+            PowerShape::Cross => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PowerShape::Line => "line",
+            PowerShape::Single => "single cell",
+            PowerShape::Cross => "cross",
+        }
+    }
+}
+
+/// Power action waiting for the player to choose a direction
+#[derive(Debug, Default)]
+pub struct PowerAim {
+    pub shape: Option<PowerShape>,
+}
+
+/// Merchant wares shown to the player, waiting for the number of the item to buy
+#[derive(Debug, Default)]
+pub struct Shop {
+    /// the zone and cell of the merchant, the shop closes when the player walks away
+    pub at: Option<(i32, (usize, usize))>,
+}
+
+impl Shop {
+    /// is the shop of the player's cell open?
+    pub fn is_open(&self, zone: &Zone) -> bool {
+        self.at == Some((zone.current, zone.cell))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn target_direction_goes_right_and_up() {
+        assert_eq!(Zone::new(350, 350, 0).target_direction(), (0, 0));
+        assert_eq!(Zone::new(350, 370, 0).target_direction(), (2, 0));
+        assert_eq!(Zone::new(350, 550, 0).target_direction(), (0, 2));
+        assert_eq!(Zone::new(350, 260, 0).target_direction(), (1, -1));
+    }
+
+    #[test]
+    fn target_direction_wraps_around_the_columns() {
+        // right from the last column is the first column of the next row
+        assert_eq!(Zone::new(390, 400, 0).target_direction(), (1, 0));
+        assert_eq!(Zone::new(400, 390, 0).target_direction(), (-1, 0));
+        assert_eq!(Zone::new(390, 350, 0).target_direction(), (-4, 0));
+        // halfway round, it goes right
+        assert_eq!(Zone::new(300, 350, 0).target_direction(), (5, 0));
+        assert_eq!(Zone::new(350, 300, 0).target_direction(), (-5, 0));
+    }
+
+    #[test]
+    fn tick_counts_down_from_the_last_turn() {
+        let mut effects = StatusEffects::default();
+        effects.add(EffectKind::Haste, 3, 10);
+        assert!(effects.tick(12).is_empty());
+        assert!(effects.has(EffectKind::Haste));
+        // the same turn again takes nothing off
+        assert!(effects.tick(12).is_empty());
+        assert_eq!(effects.tick(13), vec![EffectKind::Haste]);
+        assert!(!effects.has(EffectKind::Haste));
+        assert!(effects.effects.is_empty());
+    }
+
+    #[test]
+    fn tick_returns_only_the_effects_that_wore_off() {
+        let mut effects = StatusEffects::default();
+        effects.add(EffectKind::Frozen, 1, 0);
+        effects.add(EffectKind::Invisibility, 5, 0);
+        assert_eq!(effects.tick(2), vec![EffectKind::Frozen]);
+        assert!(effects.has(EffectKind::Invisibility));
+    }
+
+    #[test]
+    fn run_stats_count_special_encounters_only() {
+        let mut stats = RunStats::default();
+        stats.encounter(CellType::Empty);
+        stats.encounter(CellType::Gold);
+        stats.encounter(CellType::Gold);
+        assert_eq!(stats.encounters.get(&CellType::Gold), Some(&2));
+        assert_eq!(stats.encounters.get(&CellType::Empty), None);
+    }
+
+    #[test]
+    fn run_stats_add_up() {
+        let mut total = RunStats::default();
+        let mut run = RunStats { moves: 10, ..RunStats::default() };
+        run.collect(30);
+        run.spend(20);
+        run.encounter(CellType::Fountain);
+        total.add(&run);
+        total.add(&run);
+        assert_eq!(total.moves, 20);
+        assert_eq!(total.gold_collected, 60);
+        assert_eq!(total.gold_spent, 40);
+        assert_eq!(total.encounters.get(&CellType::Fountain), Some(&2));
+    }
+}
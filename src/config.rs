@@ -1,17 +1,76 @@
 //! Configuration
 use serde::{Deserialize,Serialize};
 
+use crate::components::{Run, Spell, Terrain};
+
 /// Main configuration
 #[derive(Debug, Default, Deserialize, Serialize, Clone)]
 pub struct ArrakisConfig {
+    /// name of this set of settings: high scores are ranked separately for each profile
+    pub profile: String,
+    pub screen: ScreenConfig,
     pub arena: ArenaConfig,
     pub cell: CellConfig,
     pub status: StatusConfig,
+    pub hud: HudConfig,
     pub gold: u32,
+    /// gold needed to pay the wizard
+    pub victory_gold: u32,
     pub armourer: ArmourerConfig,
     pub magician: MagicianConfig,
     pub inhabitants: usize,
     pub player: PlayerConfig,
+    /// show icons on the encounter cells already discovered
+    pub reveal_encounters: bool,
+    pub animation: AnimationConfig,
+    pub log: LogConfig,
+    pub compass: CompassMode,
+    pub oracle: OracleConfig,
+    pub map: MapConfig,
+    pub vision: VisionConfig,
+    pub day: DayConfig,
+    pub terrain: TerrainConfig,
+    pub encounters: EncounterConfig,
+    pub actions: ActionsConfig,
+    pub shields: ShieldConfig,
+    pub effects: EffectsConfig,
+    pub spells: SpellsConfig,
+    pub items: ItemsConfig,
+    pub plus: PlusConfig,
+    pub endless: EndlessConfig,
+    pub hidden_target: HiddenTargetConfig,
+    pub score: ScoreConfig,
+    /// show the encounters a shield would make while the magic key is held, the shield is put down on release
+    pub shield_preview: bool,
+}
+
+impl ArrakisConfig {
+    /// the wall threshold of the zones in the given run
+    pub fn wall_threshold(&self, run: &Run) -> i32 {
+        let threshold = self.arena.wall_threshold + self.plus.wall_threshold * run.plus as i32;
+        threshold.min(self.plus.max_wall_threshold.max(self.arena.wall_threshold))
+    }
+
+    /// the number of inhabitants in the zones of the given run
+    pub fn inhabitant_count(&self, run: &Run) -> usize {
+        self.inhabitants + self.plus.inhabitants * run.plus as usize
+    }
+}
+
+/// Logical screen size: the camera shows at least this area, scaled to the window
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ScreenConfig {
+    pub height: f32,
+    pub width: f32,
+}
+
+impl Default for ScreenConfig {
+    fn default() -> Self {
+        ScreenConfig {
+            height: 640.0,
+            width: 900.0,
+        }
+    }
 }
 
 /// Arena or zone config
@@ -55,8 +114,6 @@ impl Default for CellConfig {
 pub struct StatusConfig {
     pub height: f32,
     pub width: f32,
-    pub values_width: f32,
-    pub text_width: f32,
     pub font_size: f32,
 }
 
@@ -65,13 +122,39 @@ impl Default for StatusConfig {
         StatusConfig {
             height: 640.0,
             width: 640.0,
-            values_width: 40.0,
-            text_width: 200.0,
             font_size: 28.0,
         }
     }
 }
 
+/// HUD config: stat bars are full at the max values
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct HudConfig {
+    pub font_size: f32,
+    /// for the effects and items beside the minimap
+    pub small_font_size: f32,
+    pub strength_max: u32,
+    pub magic_max: u32,
+    pub charisma_max: u32,
+    /// the strength bar pulses below the highest of these, a sound plays when going below each one
+    pub strength_warnings: Vec<u32>,
+    pub warning_sound: bool,
+}
+
+impl Default for HudConfig {
+    fn default() -> Self {
+        HudConfig {
+            font_size: 20.0,
+            small_font_size: 14.0,
+            strength_max: 100,
+            magic_max: 20,
+            charisma_max: 30,
+            strength_warnings: vec![30, 15],
+            warning_sound: true,
+        }
+    }
+}
+
 /// Armourer encounter config
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct ArmourerConfig {
@@ -130,3 +213,527 @@ impl Default for PlayerConfig {
     }
 }
 
+
+/// Animation durations, in seconds
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct AnimationConfig {
+    pub move_duration: f32,
+    pub zone_duration: f32,
+    pub bump_duration: f32,
+}
+
+impl Default for AnimationConfig {
+    fn default() -> Self {
+        AnimationConfig {
+            move_duration: 0.12,
+            zone_duration: 0.4,
+            bump_duration: 0.15,
+        }
+    }
+}
+
+/// Message log config
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct LogConfig {
+    pub font_size: f32,
+    /// messages shown in the panel
+    pub lines: usize,
+    /// messages kept for the full history view
+    pub history: usize,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        LogConfig {
+            font_size: 14.0,
+            lines: 5,
+            history: 100,
+        }
+    }
+}
+
+/// When the compass pointing to the target zone is shown
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum CompassMode {
+    Never,
+    /// once an oracle has been consulted
+    Oracle,
+    Always,
+}
+
+impl Default for CompassMode {
+    fn default() -> Self {
+        CompassMode::Oracle
+    }
+}
+
+/// Oracle encounter config
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct OracleConfig {
+    pub gold: u32,
+}
+
+impl Default for OracleConfig {
+    fn default() -> Self {
+        OracleConfig { gold: 10 }
+    }
+}
+
+/// Minimap and world map config
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct MapConfig {
+    /// zones shown around the current zone on the minimap
+    pub radius: i32,
+    /// size of a minimap cell
+    pub cell_size: f32,
+    pub font_size: f32,
+    /// rows shown on the world map, centered on the current row
+    pub world_rows: i32,
+    pub world_font_size: f32,
+}
+
+impl Default for MapConfig {
+    fn default() -> Self {
+        MapConfig {
+            radius: 2,
+            cell_size: 22.0,
+            font_size: 9.0,
+            world_rows: 11,
+            world_font_size: 12.0,
+        }
+    }
+}
+
+/// Limited vision config: cells further than the radius or behind walls are out of sight
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct VisionConfig {
+    pub enabled: bool,
+    /// in cells
+    pub radius: f32,
+    /// radius added by a lantern
+    pub lantern_radius: f32,
+    /// lantern price at the armourer
+    pub lantern_gold: u32,
+    /// radius added by a magic light
+    pub light_radius: f32,
+    /// moves a magic light lasts
+    pub light_moves: u32,
+}
+
+impl Default for VisionConfig {
+    fn default() -> Self {
+        VisionConfig {
+            enabled: false,
+            radius: 4.0,
+            lantern_radius: 2.0,
+            lantern_gold: 30,
+            light_radius: 3.0,
+            light_moves: 20,
+        }
+    }
+}
+
+/// Day and night cycle, counted in moves
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct DayConfig {
+    /// the phases of a day, in order
+    pub phases: Vec<PhaseConfig>,
+}
+
+/// A phase of the day
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct PhaseConfig {
+    pub name: String,
+    /// logged when the phase starts
+    pub message: String,
+    /// in moves
+    pub length: u32,
+    /// strength lost per move, fractions add up over moves
+    pub cost: f32,
+    /// color laid over the zone
+    pub tint: [f32; 4],
+}
+
+impl Default for DayConfig {
+    fn default() -> Self {
+        let phase = |name: &str, message: &str, length, cost, tint| PhaseConfig {
+            name: name.to_string(),
+            message: message.to_string(),
+            length,
+            cost,
+            tint,
+        };
+        DayConfig {
+            phases: vec![
+                phase("Morning", "The sun rises", 40, 1.0, [1.0, 0.9, 0.6, 0.05]),
+                phase("Midday", "The midday heat is scorching", 30, 2.0, [1.0, 0.6, 0.1, 0.15]),
+                phase("Evening", "The sun sets", 40, 1.0, [0.8, 0.3, 0.3, 0.1]),
+                phase("Night", "Night falls, the air is cooler", 50, 0.5, [0.05, 0.05, 0.3, 0.4]),
+            ],
+        }
+    }
+}
+
+/// Config for each kind of terrain
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct TerrainConfig {
+    pub street: TerrainKindConfig,
+    pub sand: TerrainKindConfig,
+    pub shade: TerrainKindConfig,
+    pub rubble: TerrainKindConfig,
+}
+
+impl TerrainConfig {
+    /// the config of the given terrain
+    pub fn kind(&self, terrain: Terrain) -> &TerrainKindConfig {
+        match terrain {
+            Terrain::Street => &self.street,
+            Terrain::Sand => &self.sand,
+            Terrain::Shade => &self.shade,
+            Terrain::Rubble => &self.rubble,
+        }
+    }
+}
+
+impl Default for TerrainConfig {
+    fn default() -> Self {
+        let kind = |cost, frequency| TerrainKindConfig {
+            cost,
+            passable: true,
+            weight: 0,
+            frequency,
+        };
+        TerrainConfig {
+            street: kind(1.0, 50),
+            sand: kind(1.5, 25),
+            shade: kind(0.5, 15),
+            rubble: kind(2.0, 10),
+        }
+    }
+}
+
+/// A kind of terrain
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct TerrainKindConfig {
+    /// multiplies the strength cost of moving to the cell
+    pub cost: f32,
+    /// can the player and the inhabitants go there?
+    pub passable: bool,
+    /// added to the sum of the neighbours giving the encounters
+    pub weight: i32,
+    /// how often zones have this terrain, relative to the others
+    pub frequency: u32,
+}
+
+/// Encounters come from the sum of the weights of the neighbour cells
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct EncounterConfig {
+    pub wall: i32,
+    pub shield: i32,
+    pub inhabitant: i32,
+    /// sums giving each encounter
+    pub gold: i32,
+    pub oracle: i32,
+    pub fountain: i32,
+    pub armourer: i32,
+    pub magician: i32,
+}
+
+impl Default for EncounterConfig {
+    fn default() -> Self {
+        EncounterConfig {
+            wall: 2,
+            shield: 1,
+            inhabitant: 18,
+            gold: 0,
+            oracle: 8,
+            fountain: 10,
+            armourer: 12,
+            magician: 14,
+        }
+    }
+}
+
+/// Costs of the player actions
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ActionsConfig {
+    /// charisma used to keep the inhabitants still
+    pub charisma: u32,
+    /// magic used to put down a shield
+    pub shield_magic: u32,
+    /// magic used for a light, when vision is limited
+    pub light_magic: u32,
+    pub power: PowerConfig,
+    /// a fountain brings strength back up to this
+    pub fountain_strength: u32,
+}
+
+impl Default for ActionsConfig {
+    fn default() -> Self {
+        ActionsConfig {
+            charisma: 1,
+            shield_magic: 1,
+            light_magic: 1,
+            power: PowerConfig::default(),
+            fountain_strength: 100,
+        }
+    }
+}
+
+/// Power action: needs some charisma and magic, then costs stats and a random amount of gold
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct PowerConfig {
+    pub charisma_needed: u32,
+    pub magic_needed: u32,
+    pub strength: u32,
+    pub charisma: u32,
+    pub magic: u32,
+    pub gold_min: u32,
+    pub gold_max: u32,
+    /// cells cleared by a line
+    pub range: usize,
+}
+
+impl Default for PowerConfig {
+    fn default() -> Self {
+        PowerConfig {
+            charisma_needed: 10,
+            magic_needed: 2,
+            strength: 5,
+            charisma: 10,
+            magic: 1,
+            gold_min: 15,
+            gold_max: 24,
+            range: 3,
+        }
+    }
+}
+
+/// Shields wear out after some turns or after blocking some inhabitant moves
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ShieldConfig {
+    pub duration: u32,
+    pub blocks: u32,
+    pub max_per_zone: usize,
+    /// part of the magic given back when picking up a shield, less as it wears out
+    pub refund: f32,
+}
+
+impl Default for ShieldConfig {
+    fn default() -> Self {
+        ShieldConfig {
+            duration: 30,
+            blocks: 3,
+            max_per_zone: 3,
+            refund: 0.5,
+        }
+    }
+}
+
+/// Timed effects on the player, in moves
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct EffectsConfig {
+    /// inhabitants keep still after a charm
+    pub frozen: u32,
+    /// haste given by magicians
+    pub haste: u32,
+    /// strength cost of the moves while hasted, as a ratio
+    pub haste_cost: f32,
+    /// heat resistance given by fountains
+    pub heat_resistance: u32,
+    /// invisibility given by magicians
+    pub invisibility: u32,
+}
+
+impl Default for EffectsConfig {
+    fn default() -> Self {
+        EffectsConfig {
+            frozen: 1,
+            haste: 10,
+            haste_cost: 0.5,
+            heat_resistance: 15,
+            invisibility: 5,
+        }
+    }
+}
+
+/// Spells: the ones known at the start, and their magic costs
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct SpellsConfig {
+    pub start: Vec<Spell>,
+    /// magicians teach a spell not known yet on each visit
+    pub magicians_teach: bool,
+    pub blink_magic: u32,
+    /// farthest cell a blink reaches
+    pub blink_range: u32,
+    pub heal_magic: u32,
+    pub heal_strength: u32,
+    pub reveal_magic: u32,
+    pub push_magic: u32,
+    /// inhabitants this close are pushed away
+    pub push_range: u32,
+    pub sense_magic: u32,
+    pub font_size: f32,
+}
+
+impl SpellsConfig {
+    /// magic needed to cast the given spell
+    pub fn magic(&self, spell: Spell) -> u32 {
+        match spell {
+            Spell::Blink => self.blink_magic,
+            Spell::Heal => self.heal_magic,
+            Spell::Reveal => self.reveal_magic,
+            Spell::Push => self.push_magic,
+            Spell::Sense => self.sense_magic,
+        }
+    }
+}
+
+impl Default for SpellsConfig {
+    fn default() -> Self {
+        SpellsConfig {
+            start: vec![Spell::Sense],
+            magicians_teach: true,
+            blink_magic: 2,
+            blink_range: 2,
+            heal_magic: 2,
+            heal_strength: 30,
+            reveal_magic: 3,
+            push_magic: 2,
+            push_range: 2,
+            sense_magic: 1,
+            font_size: 18.,
+        }
+    }
+}
+
+/// Items: what they do, their prices at the merchants and how often they're found
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ItemsConfig {
+    /// strength a water flask gives back
+    pub flask_strength: u32,
+    /// armourers sell water flasks and charms, magicians sell map scrolls
+    pub flask_gold: u32,
+    pub charm_gold: u32,
+    pub scroll_gold: u32,
+    /// most of each item that can be carried
+    pub max_carried: u32,
+    /// chance to find an item with the gold
+    pub found_chance: f64,
+}
+
+impl Default for ItemsConfig {
+    fn default() -> Self {
+        ItemsConfig {
+            flask_strength: 40,
+            flask_gold: 10,
+            charm_gold: 10,
+            scroll_gold: 15,
+            max_carried: 2,
+            found_chance: 0.25,
+        }
+    }
+}
+
+/// New Game+: each level makes the zones harder and the target farther
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct PlusConfig {
+    /// added to the wall threshold of the arena at each level
+    pub wall_threshold: i32,
+    /// highest wall threshold, so that zones can still be crossed
+    pub max_wall_threshold: i32,
+    /// more inhabitants at each level
+    pub inhabitants: usize,
+    /// rows of zones between the start and the target added at each level
+    pub target_rows: i32,
+}
+
+impl Default for PlusConfig {
+    fn default() -> Self {
+        PlusConfig {
+            wall_threshold: 1,
+            max_wall_threshold: 7,
+            inhabitants: 2,
+            target_rows: 3,
+        }
+    }
+}
+
+/// Endless mode: the wizard moves on each time you reach him
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct EndlessConfig {
+    /// zones between a target and the next one
+    pub distance: i32,
+}
+
+impl Default for EndlessConfig {
+    fn default() -> Self {
+        EndlessConfig { distance: 4 }
+    }
+}
+
+/// Hidden target: the wizard's zone is chosen from the run seed and found from clues
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct HiddenTargetConfig {
+    pub enabled: bool,
+    /// fewest rows of zones between the start and the target
+    pub min_rows: i32,
+    /// most rows of zones between the start and the target
+    pub max_rows: i32,
+}
+
+impl Default for HiddenTargetConfig {
+    fn default() -> Self {
+        HiddenTargetConfig {
+            enabled: false,
+            min_rows: 2,
+            max_rows: 6,
+        }
+    }
+}
+
+/// Score of a run: points for each stat left, zone visited and target cleared, less some for each turn
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ScoreConfig {
+    pub strength: u32,
+    pub magic: u32,
+    pub charisma: u32,
+    pub gold: u32,
+    pub zone: u32,
+    /// each target cleared in endless mode
+    pub target: u32,
+    /// for paying the wizard
+    pub win: u32,
+    /// taken off for each turn
+    pub turn: u32,
+    /// scores kept for each profile and mode
+    pub table_size: usize,
+}
+
+impl Default for ScoreConfig {
+    fn default() -> Self {
+        ScoreConfig {
+            strength: 1,
+            magic: 2,
+            charisma: 2,
+            gold: 1,
+            zone: 5,
+            target: 200,
+            win: 500,
+            turn: 1,
+            table_size: 8,
+        }
+    }
+}
+
+/// User options, changed from the options menu and kept in the user data directory
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Options {
+    pub volume: f32,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options { volume: 1.0 }
+    }
+}
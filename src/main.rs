@@ -9,6 +9,7 @@ use amethyst::{
         types::DefaultBackend,
         RenderingBundle,
     },
+    tiles::{MortonEncoder2D, RenderTiles2D},
     ui::{RenderUi, UiBundle},
     utils::application_root_dir,
 };
@@ -18,10 +19,15 @@ mod audio;
 mod build;
 mod components;
 mod config;
+mod menu;
+mod save;
+mod scores;
 mod states;
 mod systems;
+mod wizard;
+use crate::components::TerrainTile;
 use crate::config::ArrakisConfig;
-use crate::states::InterTitle;
+use crate::menu::MainMenu;
 
 /// Game entry point
 fn main() -> amethyst::Result<()> {
@@ -44,6 +50,7 @@ fn main() -> amethyst::Result<()> {
         .with_bundle(
             RenderingBundle::<DefaultBackend>::new()
                 .with_plugin(render)
+                .with_plugin(RenderTiles2D::<TerrainTile, MortonEncoder2D>::default())
                 .with_plugin(RenderFlat2D::default())
                 .with_plugin(RenderUi::default()),
         )?
@@ -51,16 +58,31 @@ fn main() -> amethyst::Result<()> {
         .with_bundle(input_bundle)?
         .with_bundle(UiBundle::<StringBindings>::new())?
         .with_bundle(AudioBundle::default())?
-        .with(systems::StatusSystem, "status_system", &[])
-        .with(systems::MoveSystem::new(), "move_system", &["input_system"])
+        .with(systems::HudSystem::default(), "hud_system", &[])
+        .with(systems::ResizeSystem::new(), "resize_system", &[])
+        .with(systems::QueueSystem::new(), "queue_system", &["input_system"])
+        .with(systems::MoveSystem, "move_system", &["queue_system"])
+        .with(systems::ActionSystem, "action_system", &["move_system"])
+        .with(systems::EffectSystem, "effect_system", &["move_system", "action_system"])
+        .with(systems::LogSystem, "log_system", &["action_system", "effect_system"])
+        .with(systems::PreviewSystem, "preview_system", &["move_system", "action_system"])
+        .with(systems::SpellbookSystem, "spellbook_system", &["action_system"])
+        .with(systems::StatsSystem, "stats_system", &["move_system", "action_system"])
+        .with(systems::MapSystem, "map_system", &["move_system", "action_system"])
         .with(
-            systems::ActionSystem::new(),
-            "action_system",
-            &["input_system"],
+            systems::VisionSystem::default(),
+            "vision_system",
+            &["move_system", "action_system"],
+        )
+        .with(
+            systems::AnimationSystem,
+            "animation_system",
+            &["move_system", "action_system"],
         );
 
-    let mut game = Application::build(assets_dir, InterTitle::start())?
+    let mut game = Application::build(assets_dir, MainMenu::new())?
         .with_resource(arr_config)
+        .with_resource(save::load_options())
         .build(game_data)?;
     game.run();
 
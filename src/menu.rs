@@ -0,0 +1,563 @@
+//! Main, pause and options menus
+use amethyst::{
+    ecs::Entity,
+    input::{is_close_requested, is_key_down, VirtualKeyCode},
+    prelude::*,
+    ui::{Anchor, Interactable, LineMode, UiEvent, UiEventType, UiText},
+    window::Window,
+};
+use log::error;
+
+use crate::audio::{initialize_audio, Sounds};
+use crate::build::{load_font, scaled_ui};
+use crate::components::{ArcanePower, CurrentState, Run};
+use crate::config::{ArrakisConfig, Options};
+use crate::save::{is_fullscreen, save_options, set_fullscreen, SaveGame, SaveSlot};
+use crate::scores::daily_challenge;
+use crate::states::{Arrakis, InterTitle};
+
+const SELECTED_COLOR: [f32; 4] = [1.0, 0.8, 0.2, 1.0];
+const ENABLED_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+const DISABLED_COLOR: [f32; 4] = [0.4, 0.4, 0.4, 1.0];
+const MENU_WIDTH: f32 = 600.0;
+
+/// all the menu entries
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MenuItem {
+    NewGame,
+    /// a new game where the wizard keeps moving on
+    Endless,
+    /// the same world for everyone today
+    Daily,
+    Continue,
+    Load,
+    Options,
+    HighScores,
+    Quit,
+    Resume,
+    Save,
+    Restart,
+    QuitToMenu,
+    Volume,
+    Fullscreen,
+    Back,
+    /// pay the wizard
+    Pay,
+    /// walk away from the wizard without paying
+    Leave,
+    /// learn a power from the wizard
+    Learn(ArcanePower),
+}
+
+/// a menu entry with its label
+pub struct MenuEntry {
+    item: MenuItem,
+    label: String,
+    enabled: bool,
+    /// can the value be changed with left and right keys
+    adjustable: bool,
+}
+
+impl MenuEntry {
+    /// a plain entry
+    pub fn new(item: MenuItem, label: &str) -> MenuEntry {
+        MenuEntry {
+            item,
+            label: label.to_string(),
+            enabled: true,
+            adjustable: false,
+        }
+    }
+
+    /// an entry that cannot be selected
+    fn enabled(mut self, enabled: bool) -> MenuEntry {
+        self.enabled = enabled;
+        self
+    }
+
+    /// an entry whose value can be changed with left and right keys
+    fn adjustable(mut self) -> MenuEntry {
+        self.adjustable = true;
+        self
+    }
+}
+
+/// a vertical list of entries, navigated with up and down and chosen with enter (or the mouse)
+pub struct Menu {
+    title: String,
+    entries: Vec<MenuEntry>,
+    selected: usize,
+    title_entity: Option<Entity>,
+    entities: Vec<Entity>,
+}
+
+impl Menu {
+    pub fn new(title: &str, entries: Vec<MenuEntry>) -> Menu {
+        let selected = entries.iter().position(|e| e.enabled).unwrap_or(0);
+        Menu {
+            title: title.to_string(),
+            entries,
+            selected,
+            title_entity: None,
+            entities: vec![],
+        }
+    }
+
+    /// create the UI entities
+    pub fn show(&mut self, world: &mut World) {
+        self.hide(world);
+        let font = load_font(world);
+        let (font_size, center_x, center_y) = {
+            let config = world.read_resource::<ArrakisConfig>();
+            (
+                config.status.font_size,
+                config.screen.width * 0.5,
+                config.screen.height * 0.5,
+            )
+        };
+        let line_height = font_size * 1.5;
+        let top = center_y + line_height * (self.entries.len() as f32 + 2.0) * 0.5;
+
+        let (mut title_transform, title_scaled) = scaled_ui(
+            "MenuTitle",
+            center_x,
+            top,
+            MENU_WIDTH,
+            line_height * 2.0,
+            font_size * 2.0,
+        );
+        // above the HUD when shown over the game
+        title_transform.local_z = 2.;
+        let title_uit = UiText::new(
+            font.clone(),
+            self.title.clone(),
+            ENABLED_COLOR,
+            font_size * 2.0,
+            LineMode::Single,
+            Anchor::Middle,
+        );
+        self.title_entity = Some(
+            world
+                .create_entity()
+                .with(title_transform)
+                .with(title_scaled)
+                .with(title_uit)
+                .build(),
+        );
+
+        for (i, entry) in self.entries.iter().enumerate() {
+            let (mut transform, scaled) = scaled_ui(
+                &format!("MenuItem{}", i),
+                center_x,
+                top - line_height * (i as f32 + 2.0),
+                MENU_WIDTH,
+                line_height,
+                font_size,
+            );
+            transform.local_z = 2.;
+            let uit = UiText::new(
+                font.clone(),
+                entry.label.clone(),
+                ENABLED_COLOR,
+                font_size,
+                LineMode::Single,
+                Anchor::Middle,
+            );
+            let mut builder = world
+                .create_entity()
+                .with(transform)
+                .with(scaled)
+                .with(uit);
+            if entry.enabled {
+                builder = builder.with(Interactable);
+            }
+            self.entities.push(builder.build());
+        }
+        self.highlight(world);
+    }
+
+    /// delete the UI entities
+    pub fn hide(&mut self, world: &mut World) {
+        if let Some(e) = self.title_entity.take() {
+            world.delete_entity(e).unwrap();
+        }
+        for e in self.entities.drain(..) {
+            world.delete_entity(e).unwrap();
+        }
+    }
+
+    /// colour entries according to selection
+    fn highlight(&self, world: &mut World) {
+        let mut texts = world.write_storage::<UiText>();
+        for (i, (e, entry)) in self.entities.iter().zip(self.entries.iter()).enumerate() {
+            if let Some(text) = texts.get_mut(*e) {
+                text.color = if !entry.enabled {
+                    DISABLED_COLOR
+                } else if i == self.selected {
+                    SELECTED_COLOR
+                } else {
+                    ENABLED_COLOR
+                };
+            }
+        }
+    }
+
+    /// change the title text
+    fn set_title(&mut self, world: &mut World, title: &str) {
+        self.title = title.to_string();
+        if let Some(e) = self.title_entity {
+            if let Some(text) = world.write_storage::<UiText>().get_mut(e) {
+                text.text = self.title.clone();
+            }
+        }
+    }
+
+    /// change the label of an entry
+    fn set_label(&mut self, world: &mut World, item: MenuItem, label: String) {
+        if let Some(i) = self.entries.iter().position(|e| e.item == item) {
+            if let Some(text) = world.write_storage::<UiText>().get_mut(self.entities[i]) {
+                text.text = label.clone();
+            }
+            self.entries[i].label = label;
+        }
+    }
+
+    /// move the selection to the next enabled entry in the given direction
+    fn move_selection(&mut self, world: &mut World, down: bool) {
+        let count = self.entries.len();
+        let mut i = self.selected;
+        for _ in 0..count {
+            i = if down { (i + 1) % count } else { (i + count - 1) % count };
+            if self.entries[i].enabled {
+                self.selected = i;
+                break;
+            }
+        }
+        self.highlight(world);
+    }
+
+    /// handle navigation, returning the chosen entry and the direction of change
+    /// (1 for enter or right, -1 for left)
+    pub fn handle_event(&mut self, world: &mut World, event: &StateEvent) -> Option<(MenuItem, i32)> {
+        match event {
+            StateEvent::Window(event) => {
+                if is_key_down(event, VirtualKeyCode::Up) {
+                    self.move_selection(world, false);
+                } else if is_key_down(event, VirtualKeyCode::Down) {
+                    self.move_selection(world, true);
+                } else if is_key_down(event, VirtualKeyCode::Return) {
+                    return self.chosen(1);
+                } else if is_key_down(event, VirtualKeyCode::Right) {
+                    return self.adjusted(1);
+                } else if is_key_down(event, VirtualKeyCode::Left) {
+                    return self.adjusted(-1);
+                }
+                None
+            }
+            StateEvent::Ui(UiEvent { event_type, target }) => {
+                let i = self.entities.iter().position(|e| e == target)?;
+                match event_type {
+                    UiEventType::HoverStart => {
+                        self.selected = i;
+                        self.highlight(world);
+                        None
+                    }
+                    UiEventType::Click => {
+                        self.selected = i;
+                        self.chosen(1)
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// the selected entry if enabled
+    fn chosen(&self, step: i32) -> Option<(MenuItem, i32)> {
+        self.entries
+            .get(self.selected)
+            .filter(|e| e.enabled)
+            .map(|e| (e.item, step))
+    }
+
+    /// the selected entry if it can be adjusted
+    fn adjusted(&self, step: i32) -> Option<(MenuItem, i32)> {
+        self.entries
+            .get(self.selected)
+            .filter(|e| e.adjustable)
+            .and_then(|_| self.chosen(step))
+    }
+}
+
+/// Main menu, shown on startup
+pub struct MainMenu {
+    menu: Menu,
+}
+
+impl MainMenu {
+    pub fn new() -> MainMenu {
+        MainMenu {
+            menu: Menu::new(
+                "Arrakis",
+                vec![
+                    MenuEntry::new(MenuItem::NewGame, "New Game"),
+                    MenuEntry::new(MenuItem::Endless, "Endless"),
+                    MenuEntry::new(MenuItem::Daily, "Daily Challenge"),
+                    MenuEntry::new(MenuItem::Continue, "Continue")
+                        .enabled(SaveGame::exists(SaveSlot::Auto)),
+                    MenuEntry::new(MenuItem::Load, "Load").enabled(SaveGame::exists(SaveSlot::Manual)),
+                    MenuEntry::new(MenuItem::Options, "Options"),
+                    MenuEntry::new(MenuItem::HighScores, "High Scores"),
+                    MenuEntry::new(MenuItem::Quit, "Quit"),
+                ],
+            ),
+        }
+    }
+}
+
+/// switch to a game loaded from the given slot
+fn load_game(slot: SaveSlot) -> SimpleTrans {
+    match SaveGame::from_slot(slot) {
+        Some(save) => Trans::Switch(Box::new(Arrakis::load(save))),
+        None => Trans::None,
+    }
+}
+
+impl SimpleState for MainMenu {
+    fn on_start(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        let world = data.world;
+        world.delete_all();
+        if !world.has_value::<Sounds>() {
+            initialize_audio(world);
+        }
+        *world.write_resource::<CurrentState>() = CurrentState::Intertext;
+        self.menu.show(world);
+    }
+
+    fn on_stop(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        self.menu.hide(data.world);
+    }
+
+    fn on_pause(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        self.menu.hide(data.world);
+    }
+
+    fn on_resume(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        self.menu.show(data.world);
+    }
+
+    fn handle_event(
+        &mut self,
+        data: StateData<'_, GameData<'_, '_>>,
+        event: StateEvent,
+    ) -> SimpleTrans {
+        if let StateEvent::Window(event) = &event {
+            if is_close_requested(&event) {
+                return Trans::Quit;
+            }
+        }
+        match self.menu.handle_event(data.world, &event) {
+            Some((MenuItem::NewGame, _)) => Trans::Switch(Box::new(Arrakis::new())),
+            Some((MenuItem::Endless, _)) => Trans::Switch(Box::new(Arrakis::with_run(Run::endless()))),
+            Some((MenuItem::Daily, _)) => Trans::Switch(Box::new(Arrakis::with_run(daily_challenge()))),
+            Some((MenuItem::Continue, _)) => load_game(SaveSlot::Auto),
+            Some((MenuItem::Load, _)) => load_game(SaveSlot::Manual),
+            Some((MenuItem::Options, _)) => Trans::Push(Box::new(OptionsMenu::new())),
+            Some((MenuItem::HighScores, _)) => {
+                let config = data.world.read_resource::<ArrakisConfig>();
+                Trans::Push(Box::new(InterTitle::high_scores(&config, None)))
+            }
+            Some((MenuItem::Quit, _)) => Trans::Quit,
+            _ => Trans::None,
+        }
+    }
+}
+
+/// In-game pause menu, pushed on top of the gameplay state
+pub struct PauseMenu {
+    menu: Menu,
+}
+
+impl PauseMenu {
+    pub fn new() -> PauseMenu {
+        PauseMenu {
+            menu: Menu::new(
+                "Paused",
+                vec![
+                    MenuEntry::new(MenuItem::Resume, "Resume"),
+                    MenuEntry::new(MenuItem::Save, "Save"),
+                    MenuEntry::new(MenuItem::Options, "Options"),
+                    MenuEntry::new(MenuItem::Restart, "Restart"),
+                    MenuEntry::new(MenuItem::QuitToMenu, "Quit to Menu"),
+                ],
+            ),
+        }
+    }
+}
+
+/// save the running game, returning if it worked
+fn save_game(world: &World, slot: SaveSlot) -> bool {
+    match SaveGame::from_world(world).map(|save| save.save(slot)) {
+        Some(Ok(())) => true,
+        Some(Err(e)) => {
+            error!("Cannot save game: {}", e);
+            false
+        }
+        None => false,
+    }
+}
+
+impl SimpleState for PauseMenu {
+    fn on_start(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        self.menu.show(data.world);
+    }
+
+    fn on_stop(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        self.menu.hide(data.world);
+    }
+
+    fn on_pause(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        self.menu.hide(data.world);
+    }
+
+    fn on_resume(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        self.menu.show(data.world);
+    }
+
+    fn handle_event(
+        &mut self,
+        data: StateData<'_, GameData<'_, '_>>,
+        event: StateEvent,
+    ) -> SimpleTrans {
+        if let StateEvent::Window(event) = &event {
+            if is_close_requested(&event) {
+                return Trans::Quit;
+            }
+            if is_key_down(&event, VirtualKeyCode::Escape) {
+                return Trans::Pop;
+            }
+        }
+        match self.menu.handle_event(data.world, &event) {
+            Some((MenuItem::Resume, _)) => Trans::Pop,
+            Some((MenuItem::Save, _)) => {
+                let title = if save_game(data.world, SaveSlot::Manual) {
+                    "Game saved"
+                } else {
+                    "Save failed"
+                };
+                self.menu.set_title(data.world, title);
+                Trans::None
+            }
+            Some((MenuItem::Options, _)) => Trans::Push(Box::new(OptionsMenu::new())),
+            Some((MenuItem::Restart, _)) => {
+                let run = data.world.read_resource::<Run>().restart();
+                Trans::Sequence(vec![Trans::Pop, Trans::Switch(Box::new(Arrakis::with_run(run)))])
+            }
+            Some((MenuItem::QuitToMenu, _)) => {
+                save_game(data.world, SaveSlot::Auto);
+                Trans::Sequence(vec![Trans::Pop, Trans::Switch(Box::new(MainMenu::new()))])
+            }
+            _ => Trans::None,
+        }
+    }
+}
+
+/// Options menu, reachable from both the main and the pause menu
+pub struct OptionsMenu {
+    menu: Menu,
+}
+
+impl OptionsMenu {
+    pub fn new() -> OptionsMenu {
+        OptionsMenu {
+            menu: Menu::new(
+                "Options",
+                vec![
+                    MenuEntry::new(MenuItem::Volume, "").adjustable(),
+                    MenuEntry::new(MenuItem::Fullscreen, "").adjustable(),
+                    MenuEntry::new(MenuItem::Back, "Back"),
+                ],
+            ),
+        }
+    }
+
+    /// refresh the labels showing option values
+    fn update_labels(&mut self, world: &mut World) {
+        let volume = world.read_resource::<Options>().volume;
+        self.menu.set_label(
+            world,
+            MenuItem::Volume,
+            format!("Volume: {}%", (volume * 100.0).round()),
+        );
+        self.menu.set_label(
+            world,
+            MenuItem::Fullscreen,
+            format!("Fullscreen: {}", if is_fullscreen() { "On" } else { "Off" }),
+        );
+    }
+
+    /// save options and go back
+    fn back(&self, world: &World) -> SimpleTrans {
+        if let Err(e) = save_options(&world.read_resource::<Options>()) {
+            error!("Cannot save options: {}", e);
+        }
+        Trans::Pop
+    }
+}
+
+impl SimpleState for OptionsMenu {
+    fn on_start(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        self.menu.show(data.world);
+        self.update_labels(data.world);
+    }
+
+    fn on_stop(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        self.menu.hide(data.world);
+    }
+
+    fn handle_event(
+        &mut self,
+        data: StateData<'_, GameData<'_, '_>>,
+        event: StateEvent,
+    ) -> SimpleTrans {
+        if let StateEvent::Window(event) = &event {
+            if is_close_requested(&event) {
+                return Trans::Quit;
+            }
+            if is_key_down(&event, VirtualKeyCode::Escape) {
+                return self.back(data.world);
+            }
+        }
+        match self.menu.handle_event(data.world, &event) {
+            Some((MenuItem::Volume, step)) => {
+                let volume = {
+                    let mut options = data.world.write_resource::<Options>();
+                    let volume = options.volume + 0.25 * step as f32;
+                    // cycle round between mute and full volume
+                    options.volume = if volume > 1.0 {
+                        0.0
+                    } else if volume < 0.0 {
+                        1.0
+                    } else {
+                        volume
+                    };
+                    options.volume
+                };
+                data.world.write_resource::<Sounds>().volume = volume;
+                self.update_labels(data.world);
+                Trans::None
+            }
+            Some((MenuItem::Fullscreen, _)) => {
+                let result = set_fullscreen(&data.world.read_resource::<Window>(), !is_fullscreen());
+                if let Err(e) = result {
+                    error!("Cannot change fullscreen: {}", e);
+                }
+                self.update_labels(data.world);
+                Trans::None
+            }
+            Some((MenuItem::Back, _)) => self.back(data.world),
+            _ => Trans::None,
+        }
+    }
+}
@@ -0,0 +1,208 @@
+//! Saved games and other user data
+use amethyst::{
+    ecs::Join,
+    prelude::*,
+    utils::application_root_dir,
+    window::{DisplayConfig, MonitorIdent, Window},
+};
+use log::{error, warn};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+
+use crate::components::{Inventory, Player, Run, RunStats, StatusEffects, Zone};
+use crate::config::Options;
+
+/// the saved game slots
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SaveSlot {
+    /// written when quitting to the main menu, read by Continue, deleted once the run is over
+    Auto,
+    /// written by Save in the pause menu, read by Load
+    Manual,
+}
+
+/// a saved game: everything needed to rebuild the gameplay state
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SaveGame {
+    pub player: Player,
+    pub zone: Zone,
+    /// timed effects still running
+    #[serde(default)]
+    pub effects: StatusEffects,
+    #[serde(default)]
+    pub inventory: Inventory,
+    /// the kind of run: endless, New Game+...
+    #[serde(default)]
+    pub run: Run,
+    #[serde(default)]
+    pub stats: RunStats,
+}
+
+impl SaveGame {
+    /// capture the current game from the world, if a game is running
+    pub fn from_world(world: &World) -> Option<SaveGame> {
+        let players = world.read_storage::<Player>();
+        let zones = world.read_storage::<Zone>();
+        let effects = world.read_storage::<StatusEffects>();
+        let inventories = world.read_storage::<Inventory>();
+        let stats = world.read_storage::<RunStats>();
+        let save = (&players, &zones, effects.maybe(), inventories.maybe(), stats.maybe())
+            .join()
+            .next()
+            .map(|(player, zone, effects, inventory, stats)| SaveGame {
+                player: player.clone(),
+                zone: zone.clone(),
+                effects: effects.cloned().unwrap_or_default(),
+                inventory: inventory.cloned().unwrap_or_default(),
+                run: world.read_resource::<Run>().clone(),
+                stats: stats.cloned().unwrap_or_default(),
+            });
+        save
+    }
+
+    /// write the game to the given slot
+    pub fn save(&self, slot: SaveSlot) -> amethyst::Result<()> {
+        fs::create_dir_all(data_dir())?;
+        self.write(slot_path(slot))?;
+        Ok(())
+    }
+
+    /// read the game from the given slot, if there is one
+    pub fn from_slot(slot: SaveSlot) -> Option<SaveGame> {
+        let path = slot_path(slot);
+        if !path.exists() {
+            return None;
+        }
+        match SaveGame::load(&path) {
+            Ok(save) => Some(save),
+            Err(e) => {
+                warn!("Cannot load saved game {:?}: {}", path, e);
+                None
+            }
+        }
+    }
+
+    /// forget the game in the given slot, once its run is over
+    pub fn delete(slot: SaveSlot) {
+        let path = slot_path(slot);
+        if path.exists() {
+            if let Err(e) = fs::remove_file(&path) {
+                error!("Cannot delete saved game {:?}: {}", path, e);
+            }
+        }
+    }
+
+    /// is there a game in the given slot?
+    pub fn exists(slot: SaveSlot) -> bool {
+        slot_path(slot).exists()
+    }
+}
+
+/// the directory holding saved games and user options
+pub fn data_dir() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("arrakis")
+}
+
+/// the file for a saved game slot
+fn slot_path(slot: SaveSlot) -> PathBuf {
+    data_dir().join(match slot {
+        SaveSlot::Auto => "autosave.ron",
+        SaveSlot::Manual => "save.ron",
+    })
+}
+
+/// the stats of all the runs played, kept in the user data directory
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct LifetimeStats {
+    /// runs that reached an end screen
+    pub runs: u32,
+    pub wins: u32,
+    pub deaths: u32,
+    pub totals: RunStats,
+}
+
+impl LifetimeStats {
+    /// read the lifetime stats, empty if none have been saved yet
+    pub fn load_or_default() -> LifetimeStats {
+        let path = data_dir().join("stats.ron");
+        if path.exists() {
+            match LifetimeStats::load(&path) {
+                Ok(stats) => return stats,
+                Err(e) => warn!("Cannot load lifetime stats {:?}: {}", path, e),
+            }
+        }
+        LifetimeStats::default()
+    }
+
+    /// add a finished run and write the stats back
+    pub fn record(stats: &RunStats, won: bool) {
+        let mut lifetime = LifetimeStats::load_or_default();
+        lifetime.runs += 1;
+        if won {
+            lifetime.wins += 1;
+        } else {
+            lifetime.deaths += 1;
+        }
+        lifetime.totals.add(stats);
+        if let Err(e) = lifetime.save() {
+            error!("Cannot save lifetime stats: {}", e);
+        }
+    }
+
+    fn save(&self) -> amethyst::Result<()> {
+        fs::create_dir_all(data_dir())?;
+        self.write(data_dir().join("stats.ron"))?;
+        Ok(())
+    }
+}
+
+/// load user options, using the defaults if none have been saved yet
+pub fn load_options() -> Options {
+    let path = data_dir().join("options.ron");
+    if path.exists() {
+        match Options::load(&path) {
+            Ok(options) => return options,
+            Err(e) => warn!("Cannot load options {:?}: {}", path, e),
+        }
+    }
+    Options::default()
+}
+
+/// save user options
+pub fn save_options(options: &Options) -> amethyst::Result<()> {
+    fs::create_dir_all(data_dir())?;
+    options.write(data_dir().join("options.ron"))?;
+    Ok(())
+}
+
+/// the display config file, next to the game config
+fn display_config_path() -> amethyst::Result<PathBuf> {
+    Ok(application_root_dir()?.join("config").join("display.ron"))
+}
+
+/// is the display config set to fullscreen?
+pub fn is_fullscreen() -> bool {
+    display_config_path()
+        .ok()
+        .and_then(|path| DisplayConfig::load(path).ok())
+        .map_or(false, |display| display.fullscreen.is_some())
+}
+
+/// switch the window to or from fullscreen, and remember it in the display config
+pub fn set_fullscreen(window: &Window, fullscreen: bool) -> amethyst::Result<()> {
+    let monitor = window.get_current_monitor();
+    let path = display_config_path()?;
+    let mut display = DisplayConfig::load(&path)?;
+    display.fullscreen = if fullscreen {
+        MonitorIdent::from_monitor_id(window, &monitor)
+    } else {
+        None
+    };
+    window.set_fullscreen(if fullscreen { Some(monitor) } else { None });
+    display.write(&path)?;
+    Ok(())
+}
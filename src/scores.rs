@@ -0,0 +1,418 @@
+//! Scores, the local high-score table and the daily challenge results
+use amethyst::prelude::*;
+use chrono::{Datelike, Local};
+use log::{error, warn};
+use serde::{Deserialize, Serialize};
+use std::fs;
+
+use crate::components::{GameMode, Player, Run, Zone};
+use crate::config::ArrakisConfig;
+use crate::save::data_dir;
+
+/// the score of a run: what's left of the player and the zones explored, less the turns taken
+pub fn score(player: &Player, zone: &Zone, run: &Run, won: bool, config: &ArrakisConfig) -> u32 {
+    let weights = &config.score;
+    let earned = player.strength * weights.strength
+        + player.magic * weights.magic
+        + player.charisma * weights.charisma
+        + player.gold * weights.gold
+        + zone.visited.len() as u32 * weights.zone
+        + run.cleared * weights.target
+        + if won { weights.win } else { 0 };
+    earned.saturating_sub(player.turn * weights.turn)
+}
+
+/// an entry of the high-score table
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Score {
+    pub score: u32,
+    pub mode: GameMode,
+    /// New Game+ level
+    pub plus: u32,
+    /// the config profile the run was played with
+    pub profile: String,
+    pub seed: u64,
+    /// the day the run ended, as year-month-day
+    pub date: String,
+}
+
+impl Score {
+    /// the score of the current run, played today
+    pub fn new(score: u32, run: &Run, config: &ArrakisConfig) -> Score {
+        Score {
+            score,
+            mode: run.mode,
+            plus: run.plus,
+            profile: config.profile.clone(),
+            seed: run.seed,
+            date: today(),
+        }
+    }
+}
+
+/// the high-score table, kept in the user data directory
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct HighScores {
+    pub entries: Vec<Score>,
+}
+
+impl HighScores {
+    /// read the table, empty if no score has been saved yet
+    pub fn load_or_default() -> HighScores {
+        let path = data_dir().join("scores.ron");
+        if path.exists() {
+            match HighScores::load(&path) {
+                Ok(scores) => return scores,
+                Err(e) => warn!("Cannot load high scores {:?}: {}", path, e),
+            }
+        }
+        HighScores::default()
+    }
+
+    fn save(&self) -> amethyst::Result<()> {
+        fs::create_dir_all(data_dir())?;
+        self.write(data_dir().join("scores.ron"))?;
+        Ok(())
+    }
+
+    /// the scores of a profile and mode, best first: they are ranked separately
+    pub fn ranking(&self, profile: &str, mode: GameMode) -> Vec<&Score> {
+        let mut ranking: Vec<&Score> = self
+            .entries
+            .iter()
+            .filter(|s| s.profile == profile && s.mode == mode)
+            .collect();
+        ranking.sort_by(|a, b| b.score.cmp(&a.score));
+        ranking
+    }
+
+    /// add a score and save the table: the rank of the score, from 1, if it made it to the table
+    pub fn record(score: Score, size: usize) -> Option<usize> {
+        let mut table = HighScores::load_or_default();
+        let rank = table.insert(score, size)?;
+        if let Err(e) = table.save() {
+            error!("Cannot save high scores: {}", e);
+        }
+        Some(rank)
+    }
+
+    /// add a score after the ones at least as good, keeping the best ones of its profile and mode:
+    /// the rank of the score, from 1, if it made it to the table
+    pub fn insert(&mut self, score: Score, size: usize) -> Option<usize> {
+        let rank = self
+            .ranking(&score.profile, score.mode)
+            .iter()
+            .filter(|s| s.score >= score.score)
+            .count()
+            + 1;
+        if rank > size {
+            return None;
+        }
+        let (mut group, others): (Vec<Score>, Vec<Score>) = self
+            .entries
+            .drain(..)
+            .partition(|s| s.profile == score.profile && s.mode == score.mode);
+        group.sort_by(|a, b| b.score.cmp(&a.score));
+        group.insert(rank - 1, score);
+        group.truncate(size);
+        self.entries = others;
+        self.entries.extend(group);
+        Some(rank)
+    }
+
+    /// the tables of a profile for each mode, marking the given rank
+    pub fn text(&self, profile: &str, highlight: Option<(GameMode, usize)>) -> String {
+        let mut text = String::new();
+        for mode in [GameMode::Classic, GameMode::Endless, GameMode::Daily].iter() {
+            text.push_str(&format!("{}\n", mode.name()));
+            let ranking = self.ranking(profile, *mode);
+            if ranking.is_empty() {
+                text.push_str("No scores yet\n");
+            }
+            for (i, score) in ranking.iter().enumerate() {
+                let marker = if highlight == Some((*mode, i + 1)) { "> " } else { "" };
+                let plus = if score.plus > 0 { format!("  NG+{}", score.plus) } else { String::new() };
+                text.push_str(&format!(
+                    "{}{}. {}  {}  seed {:x}{}\n",
+                    marker,
+                    i + 1,
+                    score.score,
+                    score.date,
+                    score.seed,
+                    plus
+                ));
+            }
+            text.push('\n');
+        }
+        text
+    }
+}
+
+/// today's local date, as year-month-day
+pub fn today() -> String {
+    Local::today().format("%Y-%m-%d").to_string()
+}
+
+/// today's daily challenge, that counts if it's the first attempt of the day
+pub fn daily_challenge() -> Run {
+    let today = Local::today();
+    let seed = daily_seed(today.num_days_from_ce());
+    let date = today.format("%Y-%m-%d").to_string();
+    let ranked = DailyResults::start(&date);
+    Run::daily(date, seed, ranked)
+}
+
+/// the seed of a day, counted from the common era: the same for everyone on the same day
+pub fn daily_seed(day: i32) -> u64 {
+    (day as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+}
+
+/// a daily challenge played
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DailyResult {
+    pub date: String,
+    /// the text to share, empty while the attempt isn't finished
+    pub code: String,
+}
+
+/// the daily challenges played, kept in the user data directory
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct DailyResults {
+    pub results: Vec<DailyResult>,
+}
+
+impl DailyResults {
+    /// read the results, empty if no daily challenge has been played yet
+    pub fn load_or_default() -> DailyResults {
+        let path = data_dir().join("daily.ron");
+        if path.exists() {
+            match DailyResults::load(&path) {
+                Ok(results) => return results,
+                Err(e) => warn!("Cannot load daily results {:?}: {}", path, e),
+            }
+        }
+        DailyResults::default()
+    }
+
+    fn save(&self) -> amethyst::Result<()> {
+        fs::create_dir_all(data_dir())?;
+        self.write(data_dir().join("daily.ron"))?;
+        Ok(())
+    }
+
+    /// note an attempt of the day: true if it's the first one
+    pub fn begin(&mut self, date: &str) -> bool {
+        if self.results.iter().any(|r| r.date == date) {
+            return false;
+        }
+        self.results.push(DailyResult {
+            date: date.to_string(),
+            code: String::new(),
+        });
+        true
+    }
+
+    /// is the first attempt of the day still being played
+    pub fn pending(&self, date: &str) -> bool {
+        self.results.iter().any(|r| r.date == date && r.code.is_empty())
+    }
+
+    /// keep the result of the first attempt of the day: false if it was already finished
+    pub fn end(&mut self, date: &str, code: &str) -> bool {
+        match self.results.iter_mut().find(|r| r.date == date && r.code.is_empty()) {
+            Some(result) => {
+                result.code = code.to_string();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// note the first attempt of the day and save it: false if the day was already played
+    pub fn start(date: &str) -> bool {
+        let mut daily = DailyResults::load_or_default();
+        if !daily.begin(date) {
+            return false;
+        }
+        if let Err(e) = daily.save() {
+            error!("Cannot save daily results: {}", e);
+        }
+        true
+    }
+
+    /// keep the result of the attempt that counts, once
+    pub fn finish(date: &str, code: &str) {
+        let mut daily = DailyResults::load_or_default();
+        if daily.end(date, code) {
+            if let Err(e) = daily.save() {
+                error!("Cannot save daily results: {}", e);
+            }
+        }
+    }
+}
+
+/// the text to share the result of a daily challenge: date, turns and final stats
+pub fn daily_code(run: &Run, player: &Player, zone: &Zone, won: bool) -> String {
+    format!(
+        "Arrakis {} {} T{} S{} M{} C{} G{} Z{}",
+        run.date,
+        if won { "WIN" } else { "DEAD" },
+        player.turn,
+        player.strength,
+        player.magic,
+        player.charisma,
+        player.gold,
+        zone.visited.len()
+    )
+}
+
+/// end a daily challenge: keep the result if it's the attempt that counts, and say so on the end screen
+pub fn daily_result(run: &Run, player: &Player, zone: &Zone, won: bool) -> String {
+    if run.mode != GameMode::Daily {
+        String::new()
+    } else if !is_ranked(run) {
+        "Only the first attempt of the day counts".to_string()
+    } else {
+        let code = daily_code(run, player, zone, won);
+        DailyResults::finish(&run.date, &code);
+        format!("Share your daily result: {}", code)
+    }
+}
+
+/// does the run make it to the high scores: the daily challenge only on its first attempt,
+/// and only until it's finished, so that reloading a save doesn't count again
+pub fn is_ranked(run: &Run) -> bool {
+    run.mode != GameMode::Daily || (run.ranked && DailyResults::load_or_default().pending(&run.date))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn zone(visited: &[i32]) -> Zone {
+        let mut zone = Zone::new(350, 350, 0);
+        zone.visited = visited.iter().copied().collect();
+        zone
+    }
+
+    fn entry(score: u32, mode: GameMode, profile: &str) -> Score {
+        Score {
+            score,
+            mode,
+            plus: 0,
+            profile: profile.to_string(),
+            seed: 0,
+            date: "2026-10-19".to_string(),
+        }
+    }
+
+    #[test]
+    fn score_adds_the_stats_and_takes_off_the_turns() {
+        let config = ArrakisConfig::default();
+        let player = Player { turn: 30, ..Player::default() };
+        let zone = zone(&[350, 360]);
+        // 100 strength, 5 magic and 5 charisma at 2, 100 gold, 2 zones at 5
+        assert_eq!(score(&player, &zone, &Run::default(), false, &config), 230 - 30);
+        assert_eq!(score(&player, &zone, &Run::default(), true, &config), 730 - 30);
+        let run = Run { cleared: 2, ..Run::endless() };
+        assert_eq!(score(&player, &zone, &run, false, &config), 630 - 30);
+    }
+
+    #[test]
+    fn score_does_not_go_below_zero() {
+        let config = ArrakisConfig::default();
+        let player = Player { turn: 10_000, ..Player::default() };
+        assert_eq!(score(&player, &zone(&[]), &Run::default(), false, &config), 0);
+    }
+
+    #[test]
+    fn ranking_is_best_first_for_one_profile_and_mode() {
+        let table = HighScores {
+            entries: vec![
+                entry(10, GameMode::Classic, "normal"),
+                entry(30, GameMode::Classic, "normal"),
+                entry(50, GameMode::Endless, "normal"),
+                entry(40, GameMode::Classic, "hard"),
+                entry(20, GameMode::Classic, "normal"),
+            ],
+        };
+        let scores: Vec<u32> = table.ranking("normal", GameMode::Classic).iter().map(|s| s.score).collect();
+        assert_eq!(scores, vec![30, 20, 10]);
+    }
+
+    #[test]
+    fn insert_ranks_after_equal_scores() {
+        let mut table = HighScores::default();
+        assert_eq!(table.insert(entry(20, GameMode::Classic, "normal"), 3), Some(1));
+        assert_eq!(table.insert(entry(30, GameMode::Classic, "normal"), 3), Some(1));
+        assert_eq!(table.insert(entry(20, GameMode::Classic, "normal"), 3), Some(3));
+        let scores: Vec<u32> = table.ranking("normal", GameMode::Classic).iter().map(|s| s.score).collect();
+        assert_eq!(scores, vec![30, 20, 20]);
+    }
+
+    #[test]
+    fn insert_truncates_each_table_separately() {
+        let mut table = HighScores::default();
+        for score in [10, 20, 30].iter() {
+            table.insert(entry(*score, GameMode::Classic, "normal"), 2);
+        }
+        table.insert(entry(5, GameMode::Endless, "normal"), 2);
+        table.insert(entry(5, GameMode::Classic, "hard"), 2);
+        let scores: Vec<u32> = table.ranking("normal", GameMode::Classic).iter().map(|s| s.score).collect();
+        assert_eq!(scores, vec![30, 20]);
+        // a full table refuses worse scores, other modes and profiles are kept
+        assert_eq!(table.insert(entry(15, GameMode::Classic, "normal"), 2), None);
+        assert_eq!(table.ranking("normal", GameMode::Endless).len(), 1);
+        assert_eq!(table.ranking("hard", GameMode::Classic).len(), 1);
+        assert_eq!(table.entries.len(), 4);
+    }
+
+    #[test]
+    fn daily_seed_is_the_same_all_day_and_changes_every_day() {
+        let day = NaiveDate::from_ymd(2026, 10, 19).num_days_from_ce();
+        assert_eq!(daily_seed(day), daily_seed(day));
+        assert_ne!(daily_seed(day), daily_seed(day + 1));
+        assert_eq!(daily_seed(day), (day as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15));
+    }
+
+    #[test]
+    fn daily_code_shows_the_outcome_and_final_stats() {
+        let run = Run::daily("2026-10-19".to_string(), 1, true);
+        let player = Player { turn: 42, gold: 7, ..Player::default() };
+        assert_eq!(
+            daily_code(&run, &player, &zone(&[350, 360, 460]), true),
+            "Arrakis 2026-10-19 WIN T42 S100 M5 C5 G7 Z3"
+        );
+        assert_eq!(
+            daily_code(&run, &Player { strength: 0, ..player }, &zone(&[350]), false),
+            "Arrakis 2026-10-19 DEAD T42 S0 M5 C5 G7 Z1"
+        );
+    }
+
+    #[test]
+    fn only_the_first_attempt_of_the_day_counts() {
+        let mut daily = DailyResults::default();
+        assert!(daily.begin("2026-10-19"));
+        assert!(daily.pending("2026-10-19"));
+        // starting again the same day, or reloading a save, doesn't make a new attempt
+        assert!(!daily.begin("2026-10-19"));
+        assert!(daily.end("2026-10-19", "first"));
+        assert!(!daily.pending("2026-10-19"));
+        // the result is only kept once
+        assert!(!daily.end("2026-10-19", "second"));
+        assert_eq!(daily.results.len(), 1);
+        assert_eq!(daily.results[0].code, "first");
+        // the next day is a new challenge
+        assert!(daily.begin("2026-10-20"));
+    }
+
+    #[test]
+    fn runs_other_than_the_daily_challenge_are_ranked() {
+        assert!(is_ranked(&Run::default()));
+        assert!(is_ranked(&Run::endless()));
+        assert!(!is_ranked(&Run::daily("2026-10-19".to_string(), 1, false)));
+    }
+}
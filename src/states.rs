@@ -1,22 +1,57 @@
 //! States
-use amethyst::{ecs::{Join,Entity}, input::*, prelude::*, ui::Anchor};
+use amethyst::{core::Hidden, ecs::{Join,Entity}, input::*, prelude::*, ui::Anchor};
 
+use crate::arrakis::{next_target, wizard_cell};
 use crate::build::*;
-use crate::components::{Action, Cell, Inhabitant, Player, CurrentState, Zone};
+use crate::components::{Action, ActionQueue, GameMode, Inhabitant, LogHistory, MapCell, MapView, MessageLog, Player, PowerAim, CurrentState, Shop, Inventory, Run, RunStats, TargetClues, ShieldPreview, Spellbook, SpellbookPanel, StatusEffects, WorldMap, Zone};
 use crate::config::ArrakisConfig;
-use crate::audio::{initialize_audio};
+use crate::menu::PauseMenu;
+use crate::save::{LifetimeStats, SaveGame, SaveSlot};
+use crate::scores::{daily_result, score, HighScores, Score};
+use crate::wizard::WizardEncounter;
 use std::ops::Deref;
 
 /// Game State
-pub struct Arrakis;
+pub struct Arrakis {
+    /// saved game to resume instead of starting a new game
+    save: Option<SaveGame>,
+    /// the kind of run to start
+    run: Run,
+    /// is the player on the wizard's cell: he only speaks when the player arrives, so that he can be left
+    at_wizard: bool,
+}
+
+impl Arrakis {
+    /// start a new game
+    pub fn new() -> Arrakis {
+        Arrakis::with_run(Run::default())
+    }
+
+    /// start a new game of the given kind: endless, New Game+...
+    pub fn with_run(run: Run) -> Arrakis {
+        Arrakis { save: None, run, at_wizard: false }
+    }
+
+    /// resume a saved game
+    pub fn load(save: SaveGame) -> Arrakis {
+        let run = save.run.clone();
+        Arrakis { save: Some(save), run, at_wizard: false }
+    }
+}
 
 impl SimpleState for Arrakis {
     fn on_start(&mut self, data: StateData<'_, GameData<'_, '_>>) {
         let world = data.world;
         world.delete_all();
 
-        world.register::<Cell>();
         world.register::<Inhabitant>();
+        world.register::<LogHistory>();
+        world.register::<MapCell>();
+        world.register::<WorldMap>();
+        world.register::<ShieldPreview>();
+        world.register::<StatusEffects>();
+        world.register::<Inventory>();
+        world.register::<SpellbookPanel>();
         
 
         let sprite_sheet_handle = load_sprite_sheet(world);
@@ -27,28 +62,130 @@ impl SimpleState for Arrakis {
       
        
         initialize_terrain(world, &sprite_sheet_handle);
-        initialize_player(world, sprite_sheet_handle, font.clone());
-        initialize_text(world, font);
+        initialize_shield_preview(world, &sprite_sheet_handle);
+        if self.save.is_none() && self.run.seed == 0 {
+            // a new run, with its own hidden target
+            self.run.seed = rand::random();
+        }
+        world.insert(self.run.clone());
+        initialize_player(world, sprite_sheet_handle, self.save.take());
+        initialize_day_tint(world);
+        initialize_hud(world);
+        initialize_log(world, font.clone());
+        initialize_spellbook(world, font.clone());
+        initialize_map(world, font);
+        world.insert(ActionQueue::default());
+        world.insert(MapView::default());
+        world.insert(PowerAim::default());
+        world.insert(Shop::default());
+        world.insert(Spellbook::default());
+        let (history, target, hidden) = {
+            let config = world.read_resource::<ArrakisConfig>();
+            let zones = world.read_storage::<Zone>();
+            let zone = (&zones).join().next();
+            let target = zone.map_or(String::new(), |zone| zone.target_name());
+            (config.log.history, target, zone.map_or(false, |zone| !zone.target_known()))
+        };
+        let mut log = MessageLog::new(history);
+        log.add(match self.run.mode {
+            GameMode::Classic => format!("Find the wizard in zone {}, L shows all messages", target),
+            GameMode::Endless => format!("Find the wizard in zone {}, he moves on each time you reach him", target),
+            GameMode::Daily if self.run.ranked => format!(
+                "Daily challenge of {}: find the wizard in zone {}, only this first attempt counts", self.run.date, target),
+            GameMode::Daily => format!(
+                "Daily challenge of {}, already played: find the wizard in zone {} for practice", self.run.date, target),
+        });
+        if hidden {
+            log.add("Oracles, map scrolls and charmed inhabitants give clues to the wizard's zone");
+        }
+        world.insert(log);
+        *world.write_resource::<CurrentState>() = CurrentState::Gameplay;
+    }
+
+    fn on_stop(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        *data.world.write_resource::<CurrentState>() = CurrentState::Intertext;
+    }
+
+    fn on_pause(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        *data.world.write_resource::<CurrentState>() = CurrentState::Intertext;
+        // moves buffered during an animation must not run behind the menu or the wizard
+        data.world.write_resource::<ActionQueue>().actions.clear();
+    }
+
+    fn on_resume(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        *data.world.write_resource::<CurrentState>() = CurrentState::Gameplay;
+    }
+
+    fn handle_event(
+        &mut self,
+        _data: StateData<'_, GameData<'_, '_>>,
+        event: StateEvent,
+    ) -> SimpleTrans {
+        if let StateEvent::Window(event) = &event {
+            if is_close_requested(&event) {
+                return Trans::Quit;
+            }
+            if is_key_down(&event, VirtualKeyCode::Escape) {
+                return Trans::Push(Box::new(PauseMenu::new()));
+            }
+        }
+        Trans::None
     }
 
     fn update(&mut self, data: &mut StateData<'_, GameData<'_, '_>>) -> SimpleTrans {
         let world = &data.world;
-        // change state: dead, restart, help screen, success
-        for (player,zone) in (&mut world.write_storage::<Player>(),&world.read_storage::<Zone>()).join() {
+        let config = world.read_resource::<ArrakisConfig>();
+        let mut run = world.write_resource::<Run>();
+        let wizard = wizard_cell(&config);
+        // change state: dead, restart, help screen, wizard
+        for (player,zone) in (&mut world.write_storage::<Player>(),&mut world.write_storage::<Zone>()).join() {
             if player.strength == 0 {
-                return Trans::Switch(Box::new(InterTitle::dead()));
+                let stats = (&world.read_storage::<RunStats>()).join().next().cloned().unwrap_or_default();
+                LifetimeStats::record(&stats, false);
+                // a finished run can't be continued
+                SaveGame::delete(SaveSlot::Auto);
+                let daily = daily_result(&run, player, zone, false);
+                if run.mode == GameMode::Endless {
+                    // endless runs only end this way, they make it to the high scores too
+                    let points = score(player, zone, &run, false, &config);
+                    let rank = HighScores::record(Score::new(points, &run, &config), config.score.table_size);
+                    return Trans::Sequence(vec![
+                        Trans::Switch(Box::new(InterTitle::dead(&run, &stats, &daily))),
+                        Trans::Push(Box::new(InterTitle::high_scores(&config, rank.map(|r| (run.mode, r))))),
+                    ]);
+                }
+                return Trans::Switch(Box::new(InterTitle::dead(&run, &stats, &daily)));
             }
             if let Some(Action::Restart) = player.action {
                 player.action = None;
-                return Trans::Switch(Box::new(Arrakis));
+                return Trans::Switch(Box::new(Arrakis::with_run(run.restart())));
             }
             if let Some(Action::Help) = player.action {
                 player.action = None;
-                player.current_state = CurrentState::Intertext;
-                return Trans::Push(Box::new(InterTitle::help()));
+                return Trans::Push(Box::new(InterTitle::help(&config, zone)));
             }
-            if zone.current==zone.target && zone.cell==(10,10) && player.gold>=400 {
-                return Trans::Switch(Box::new(InterTitle::success()));
+            let at_wizard = zone.current==zone.target && zone.cell==wizard;
+            let arrived = at_wizard && !self.at_wizard;
+            self.at_wizard = at_wizard;
+            if arrived {
+                match run.mode {
+                    GameMode::Classic | GameMode::Daily if player.gold>=config.victory_gold => {
+                        return Trans::Push(Box::new(WizardEncounter::new(&config)));
+                    }
+                    GameMode::Classic | GameMode::Daily => {},
+                    GameMode::Endless => {
+                        // the wizard vanishes, to wait further away
+                        run.cleared += 1;
+                        zone.target = next_target(zone, player.turn, &run, &config);
+                        zone.clues = TargetClues { hidden: zone.clues.hidden, ..TargetClues::default() };
+                        if let Some(wiz) = zone.wizard.take() {
+                            let entities = world.entities();
+                            entities.delete(entities.entity(wiz)).unwrap();
+                        }
+                        world.write_resource::<MessageLog>().add(format!(
+                            "Target {} cleared! The wizard now waits for you in zone {}", run.cleared, zone.target_name()));
+                    }
+                }
             }
         }
 
@@ -71,63 +208,122 @@ pub struct InterTitle {
     entity: Option<Entity>,
     /// font ratio
     font_ratio: f32,
+    /// the run started on restart
+    next: Run,
+    /// the New Game+ run started with N, if offered
+    new_game_plus: Option<Run>,
 }
 
 impl InterTitle {
-    /// dead message
-    pub fn dead() -> InterTitle {
+    /// dead message, with the stats of the run and the daily challenge result
+    pub fn dead(run: &Run, stats: &RunStats, daily: &str) -> InterTitle {
+        let message = match run.mode {
+            GameMode::Endless => format!(
+                "You are DEAD!\n{} target(s) cleared\n\n{}\n\nPress R to restart",
+                run.cleared,
+                stats.summary()
+            ),
+            _ => format!("You are DEAD!\n\n{}\n{}\n\nPress R to restart", stats.summary(), daily),
+        };
         InterTitle {
-            message: "You are DEAD!\nPress R to restart".to_string(),
+            message,
             key: VirtualKeyCode::R,
             restart: true,
             anchor: Anchor::TopMiddle,
             entity: None,
-            font_ratio: 2.0,
+            font_ratio: 1.0,
+            next: run.restart(),
+            new_game_plus: None,
         }
     }
 
-    /// success message
-    pub fn success() -> InterTitle {
+    /// ending told after learning from the wizard, offering New Game+
+    pub fn ending(message: String, run: &Run, new_game_plus: Run) -> InterTitle {
         InterTitle {
-            message: "You WIN!\nPress R to have another go".to_string(),
+            message,
             key: VirtualKeyCode::R,
             restart: true,
             anchor: Anchor::TopMiddle,
             entity: None,
-            font_ratio: 2.0,
+            font_ratio: 0.8,
+            next: run.restart(),
+            new_game_plus: Some(new_game_plus),
         }
     }
 
-    /// start message
-    pub fn start() -> InterTitle {
+    /// high scores of the config profile, shown from the main menu and after a win, marking the given rank
+    pub fn high_scores(config: &ArrakisConfig, highlight: Option<(GameMode, usize)>) -> InterTitle {
         InterTitle {
-            message: "Welcome to Arrakis\nPress S to start".to_string(),
-            key: VirtualKeyCode::S,
-            restart: true,
+            message: format!(
+                "High Scores, {} profile\n\n{}Press Escape to go back",
+                config.profile,
+                HighScores::load_or_default().text(&config.profile, highlight)
+            ),
+            key: VirtualKeyCode::Escape,
+            restart: false,
             anchor: Anchor::TopMiddle,
             entity: None,
-            font_ratio: 2.0,
+            font_ratio: 0.6,
+            next: Run::default(),
+            new_game_plus: None,
         }
     }
 
     /// help message
-    pub fn help() -> InterTitle {
+    pub fn help(config: &ArrakisConfig, zone: &Zone) -> InterTitle {
+        let costs = &config.actions;
+        let power = &costs.power;
         InterTitle {
-            message: "Arrow keys to move
-C to stop inhabitants for one turn
-M to put down a magic shield
-P to use magic to change terrain
+            message: format!("Arrow keys to move
+C to stop inhabitants for {} turn(s): {} charisma
+M to put down a magic shield: {} magic, U to pick it up again
+   hold M to see the encounters it would make, if the preview is enabled
+P to blast walls, then an arrow for the direction, P again for another shape:
+   needs {} charisma and {} magic, costs {} strength, {} charisma, {} magic and {} to {} gold
+V to light up your way when vision is limited: {} magic
+1 to drink from a water flask, 2 to read a map scroll, 3 to use a charm
+T on a merchant to see what he sells, then the item number to buy it
+B to open your spellbook, arrows to choose a spell and Enter to cast it:
+   blink {}, heal {}, reveal {}, push {}, sense the wizard {} magic
 R to restart the game if you're stuck
-                
-Reach the wizard of Arrakis in zone 350 with 400 Gold
+L to show all messages, Tab to show the world map
+Escape to open the menu
+
+Reach the wizard of Arrakis in zone {} with {} Gold
+Win to start a New Game+ with your power, or try the endless mode and the daily challenge from the main menu
 Watch out for special places that will impact your stats
-Press R to resume"
-                .to_string(),
+Fountains bring your strength back up to {} and keep the heat away
+Magicians make you quick and unseen for a while, and teach you spells
+Oracles can show you the way, and give clues when the wizard's zone is hidden
+Moving in the midday heat is tiring, nights are cooler
+Press R to resume",
+                config.effects.frozen,
+                costs.charisma,
+                costs.shield_magic,
+                power.charisma_needed,
+                power.magic_needed,
+                power.strength,
+                power.charisma,
+                power.magic,
+                power.gold_min,
+                power.gold_max,
+                costs.light_magic,
+                config.spells.blink_magic,
+                config.spells.heal_magic,
+                config.spells.reveal_magic,
+                config.spells.push_magic,
+                config.spells.sense_magic,
+                zone.target_name(),
+                config.victory_gold,
+                costs.fountain_strength,
+            ),
             key: VirtualKeyCode::R,
             restart: false,
             anchor: Anchor::MiddleLeft,
             entity: None,
-            font_ratio: 1.0,
+            font_ratio: 0.75,
+            next: Run::default(),
+            new_game_plus: None,
         }
     }
 }
@@ -135,7 +331,6 @@ Press R to resume"
 impl SimpleState for InterTitle {
     fn on_start(&mut self, data: StateData<'_, GameData<'_, '_>>) {
         let world = data.world;
-        initialize_audio(world);
 
         if self.restart{
             world.delete_all();
@@ -146,6 +341,19 @@ impl SimpleState for InterTitle {
         self.entity=Some(initialize_inter_text(world, font, &self.message, &config, &self.anchor, self.font_ratio));
     }
 
+    fn on_pause(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        // the high scores can be shown over the ending
+        if let Some(e) = self.entity {
+            data.world.write_storage::<Hidden>().insert(e, Hidden).unwrap();
+        }
+    }
+
+    fn on_resume(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        if let Some(e) = self.entity {
+            data.world.write_storage::<Hidden>().remove(e);
+        }
+    }
+
     fn handle_event(
         &mut self,
         data: StateData<'_, GameData<'_, '_>>,
@@ -154,12 +362,18 @@ impl SimpleState for InterTitle {
         if let StateEvent::Window(event) = &event {
             if is_close_requested(&event) {
                 Trans::Quit
-            } else if is_key_down(&event, self.key) {
+            } else if self.new_game_plus.is_some() && is_key_up(&event, VirtualKeyCode::N) {
+                if let Some(e) = self.entity.take(){
+                    data.world.delete_entity(e).unwrap();
+                }
+                Trans::Switch(Box::new(Arrakis::with_run(self.new_game_plus.take().unwrap())))
+            } else if is_key_up(&event, self.key) {
+                // react on release, so the key press is not also seen as a gameplay action
                 if let Some(e) = self.entity.take(){
                     data.world.delete_entity(e).unwrap();
                 }
                 if self.restart {
-                    Trans::Switch(Box::new(Arrakis))
+                    Trans::Switch(Box::new(Arrakis::with_run(self.next.clone())))
                 } else {
                     Trans::Pop
                 }
@@ -1,118 +1,346 @@
 //!   User actions system
 use amethyst::assets::AssetStorage;
 use amethyst::audio::{output::Output, Source};
-use amethyst::core::Transform;
-use amethyst::ecs::{Entities, Join, Read, ReadExpect, ReadStorage, System, World, WriteStorage};
-use amethyst::input::{InputEvent, StringBindings};
-use amethyst::renderer::{resources::Tint, SpriteRender};
-use amethyst::shred::DynamicSystemData;
-use amethyst::shrev::{EventChannel, ReaderId};
+use amethyst::core::{math::Vector3, Transform};
+use amethyst::ecs::{Entities, Entity, Join, Read, ReadExpect, ReadStorage, System, Write, WriteStorage};
+use amethyst::tiles::TileMap;
 use rand::Rng;
 use std::ops::Deref;
 
-use crate::arrakis::{add_shield, power_clear};
+use super::animation::animate_move;
+use crate::arrakis::{
+    add_shield, blink_target, buy_item, learn_clue, merchant_name, merchant_wares, perform_move, power_clear, push_inhabitants,
+    refund_shield, remove_shield, reveal_encounters, run_rng, sense_wizard, wares_text,
+};
 use crate::audio::{play_sound, SoundHandler, Sounds};
-use crate::build::show_walls;
-use crate::components::{Action, Cell, CurrentState, Player, Shield, Zone};
+use crate::build::{place_inhabitants, show_terrain};
+use crate::components::{
+    Action, ActionQueue, ArcanePower, CellType, ClueSource, CurrentState, EffectKind, Inhabitant, Inventory, Item, MapView, MessageLog, Player, PowerAim, PowerShape, Shop, Spell, Spellbook,
+    Run, RunStats, StatusEffects, TerrainTile, Tween, Zone,
+};
 use crate::config::ArrakisConfig;
 
-pub struct ActionSystem {
-    reader: Option<ReaderId<InputEvent<StringBindings>>>,
-}
-
-impl ActionSystem {
-    pub fn new() -> Self {
-        Self { reader: None }
-    }
-}
+pub struct ActionSystem;
 
 impl<'s> System<'s> for ActionSystem {
     type SystemData = (
         WriteStorage<'s, Player>,
         WriteStorage<'s, Zone>,
+        WriteStorage<'s, StatusEffects>,
+        WriteStorage<'s, Inventory>,
+        WriteStorage<'s, RunStats>,
+        WriteStorage<'s, TileMap<TerrainTile>>,
+        WriteStorage<'s, Tween>,
         WriteStorage<'s, Transform>,
-        WriteStorage<'s, SpriteRender>,
-        WriteStorage<'s, Shield>,
-        ReadStorage<'s, Cell>,
-        WriteStorage<'s, Tint>,
+        ReadStorage<'s, Inhabitant>,
         Entities<'s>,
-        Read<'s, EventChannel<InputEvent<StringBindings>>>,
+        Write<'s, ActionQueue>,
+        Write<'s, PowerAim>,
+        Write<'s, Spellbook>,
+        Write<'s, Shop>,
+        Write<'s, MessageLog>,
+        Write<'s, MapView>,
+        Read<'s, CurrentState>,
+        Read<'s, Run>,
         Read<'s, ArrakisConfig>,
         Read<'s, AssetStorage<Source>>,
         ReadExpect<'s, Sounds>,
         Option<Read<'s, Output>>,
     );
 
-    /// register event channel
-    fn setup(&mut self, w: &mut World) {
-        <Self::SystemData as DynamicSystemData>::setup(&self.accessor(), w);
-        self.reader = Some(
-            w.fetch_mut::<EventChannel<InputEvent<StringBindings>>>()
-                .register_reader(),
-        );
-    }
-
     /// listen to actions other than move
     fn run(
         &mut self,
         (
             mut players,
             mut zones,
+            mut effects,
+            mut inventories,
+            mut stats,
+            mut tilemaps,
+            mut tweens,
             mut transforms,
-            mut sprites,
-            mut shields,
-            cells,
-            mut tints,
+            inhabitants,
             entities,
-            event,
+            mut queue,
+            mut aim,
+            mut book,
+            mut shop,
+            mut log,
+            mut map_view,
+            state,
+            run,
             config,
             storage,
             sounds,
             audio_output,
         ): Self::SystemData,
     ) {
-        for event in event.read(self.reader.as_mut().unwrap()) {
-            if let InputEvent::ActionPressed(action) = event {
-                for (player, zone) in (&mut players, &mut zones).join() {
-                    if player.current_state == CurrentState::Gameplay {
-                        let oh = match action.as_ref() {
-                            "charisma" if player.charisma > 0 => {
-                                player.charisma -= 1;
-                                player.action = Some(Action::Charisma);
+        // no actions behind a menu, and wait for the previous move to be animated
+        if *state != CurrentState::Gameplay || (&tweens).join().next().is_some() {
+            return;
+        }
+        let costs = &config.actions;
+        let power = &costs.power;
+        let spells = &config.spells;
+        // while aiming the power or with the spellbook open, the arrows choose the direction or the spell
+        let next = if aim.shape.is_some() || book.open {
+            queue.actions.pop_front()
+        } else {
+            queue.pop_other()
+        };
+        if let Some(action) = next {
+            let inhabitant_positions: Vec<(Entity, Vector3<f32>)> = (&entities, &inhabitants, &transforms)
+                .join()
+                .map(|(entity, _, transform)| (entity, *transform.translation()))
+                .collect();
+            let mut pushed = 0;
+            for (entity, player, zone, effects, inventory, stats, transform) in
+                (&entities, &mut players, &mut zones, &mut effects, &mut inventories, &mut stats, &mut transforms).join()
+            {
+                let oh = if book.open {
+                    // the spellbook takes all the keys until it's closed
+                    match (book.blinking, direction(&action)) {
+                        (true, Some(direction)) => {
+                            book.blinking = false;
+                            book.open = false;
+                            match blink_target(zone, direction, &config) {
+                                Some(cell) => {
+                                    player.magic = player.magic.saturating_sub(spells.blink_magic);
+                                    let from = *transform.translation();
+                                    zone.cell = cell;
+                                    log.add("You blink across");
+                                    perform_move(zone, transform, player, effects, inventory, stats, &run, &config, &mut log);
+                                    stats.encounter(zone.current_type);
+                                    show_terrain(zone, &mut tilemaps, &config);
+                                    animate_move(entity, from, transform, &mut tweens, config.animation.move_duration);
+                                    Some(SoundHandler::new(&|s: &'s Sounds| &s.magic_sfx))
+                                }
+                                None => {
+                                    log.add("There is nowhere to blink to");
+                                    None
+                                }
+                            }
+                        }
+                        (true, None) => {
+                            if action == "spells" {
+                                book.blinking = false;
+                                book.open = false;
+                            }
+                            None
+                        }
+                        (false, _) => match action.as_ref() {
+                            "up" => {
+                                book.selected = book.selected.saturating_sub(1);
+                                None
+                            }
+                            "down" => {
+                                book.selected = (book.selected + 1).min(player.spells.len().saturating_sub(1));
+                                None
+                            }
+                            "spells" => {
+                                book.open = false;
+                                None
+                            }
+                            "cast" => match player.spells.get(book.selected).copied() {
+                                Some(spell) if player.magic < spells.magic(spell) => {
+                                    log.add(format!(
+                                        "{} needs {} magic, you have {}",
+                                        spell.name(),
+                                        spells.magic(spell),
+                                        player.magic
+                                    ));
+                                    None
+                                }
+                                Some(Spell::Blink) => {
+                                    book.blinking = true;
+                                    log.add("Blink: choose a direction with the arrows, B to give up");
+                                    None
+                                }
+                                Some(spell) => {
+                                    player.magic -= spells.magic(spell);
+                                    book.open = false;
+                                    match spell {
+                                        Spell::Heal => {
+                                            player.strength += spells.heal_strength;
+                                            log.add(format!("You heal yourself and get {} strength back", spells.heal_strength));
+                                        }
+                                        Spell::Reveal => {
+                                            reveal_encounters(zone, &config);
+                                            show_terrain(zone, &mut tilemaps, &config);
+                                            log.add("Your spell reveals the fountains, merchants and gold of this zone");
+                                        }
+                                        Spell::Push => {
+                                            pushed = push_inhabitants(zone, &config);
+                                            log.add(match pushed {
+                                                0 => "Nobody is close enough to push away".to_string(),
+                                                n => format!("Your spell pushes {} inhabitants away", n),
+                                            });
+                                        }
+                                        Spell::Sense => log.add(sense_wizard(zone)),
+                                        Spell::Blink => {}
+                                    }
+                                    Some(SoundHandler::new(&|s: &'s Sounds| &s.magic_sfx))
+                                }
+                                None => None,
+                            },
+                            _ => None,
+                        },
+                    }
+                } else {
+                    match (aim.shape, direction(&action)) {
+                        (Some(shape), Some(direction)) => {
+                            aim.shape = None;
+                            player.strength = player.strength.saturating_sub(power.strength);
+                            player.charisma = player.charisma.saturating_sub(power.charisma);
+                            player.magic = player.magic.saturating_sub(power.magic);
+                            let mut rng = run_rng(&run, zone.current, player.turn);
+                            let cost = if player.power == Some(ArcanePower::Stormcaller) {
+                                0
+                            } else {
+                                rng.gen_range(power.gold_min..=power.gold_max.max(power.gold_min)).min(player.gold)
+                            };
+                            player.gold -= cost;
+                            stats.spend(cost);
+                            pushed = power_clear(zone, zone.cell, shape, direction, &config);
+                            stats.powers += 1;
+                            show_terrain(zone, &mut tilemaps, &config);
+                            log.add(format!("Your power clears the walls, it costs you {} gold", cost));
+                            if pushed > 0 {
+                                log.add("Your power pushes the inhabitants back");
+                            }
+                            Some(SoundHandler::new(&|s: &'s Sounds| &s.power_sfx))
+                        }
+                        (Some(shape), None) => {
+                            if action == "power" {
+                                aim.shape = shape.next();
+                                match aim.shape {
+                                    Some(next) => log.add(aim_message(next)),
+                                    None => log.add("You hold back your power"),
+                                }
+                            } else {
+                                // other actions wait until the power is used or held back
+                                log.add("You are aiming your power: choose a direction, or P until you hold it back");
+                            }
+                            None
+                        }
+                        (None, _) => match action.as_ref() {
+                            "buy" if shop.is_open(zone) => {
+                                shop.at = None;
+                                log.add(format!("You leave the {}", merchant_name(zone.current_type)));
+                                None
+                            }
+                            "buy" => {
+                                let wares = merchant_wares(zone.current_type, inventory, &config);
+                                if wares.is_empty() {
+                                    log.add("There is no merchant here");
+                                } else {
+                                    shop.at = Some((zone.current, zone.cell));
+                                    log.add(format!(
+                                        "The {} sells {}: press a number to buy, T when you're done",
+                                        merchant_name(zone.current_type),
+                                        wares_text(&wares)
+                                    ));
+                                }
+                                None
+                            }
+                            "flask" | "scroll" | "charm" if shop.is_open(zone) => {
+                                // the item keys choose what to buy while the shop is open
+                                let number = match action.as_ref() {
+                                    "flask" => 1,
+                                    "scroll" => 2,
+                                    _ => 3,
+                                };
+                                if buy_item(zone, number, player, inventory, stats, &config, &mut log) {
+                                    match zone.current_type {
+                                        CellType::Magician => Some(SoundHandler::new(&|s: &'s Sounds| &s.magician_sfx)),
+                                        _ => Some(SoundHandler::new(&|s: &'s Sounds| &s.armourer_sfx)),
+                                    }
+                                } else {
+                                    None
+                                }
+                            }
+                            "charisma" if player.charisma >= costs.charisma => {
+                                player.charisma -= costs.charisma;
+                                effects.add(EffectKind::Frozen, config.effects.frozen, player.turn);
+                                log.add(match config.effects.frozen {
+                                    1 => "Your charm keeps the inhabitants still for a turn".to_string(),
+                                    turns => format!("Your charm keeps the inhabitants still for {} turns", turns),
+                                });
+                                if !zone.inhabitants.is_empty() {
+                                    if let Some(message) = learn_clue(zone, ClueSource::Inhabitant) {
+                                        log.add(message);
+                                    }
+                                }
                                 Some(SoundHandler::new(&|s: &'s Sounds| &s.charisma_sfx))
                             }
+                            "charisma" => {
+                                log.add(format!("Charm needs {} charisma, you have {}", costs.charisma, player.charisma));
+                                None
+                            }
+                            "magic" if zone.shields.len() >= config.shields.max_per_zone => {
+                                log.add(format!("You cannot hold more than {} shields in a zone", config.shields.max_per_zone));
+                                None
+                            }
                             "magic"
-                                if player.magic > 0
+                                if player.magic >= costs.shield_magic
                                     && zone.cells[zone.cell.0][zone.cell.1] == 0 =>
                             {
-                                player.magic -= 1;
-                                let mut h = None;
-                                for sprite in (&mut sprites).join() {
-                                    h = Some(sprite.sprite_sheet.clone());
-                                    break;
+                                player.magic -= costs.shield_magic;
+                                add_shield(zone, zone.cell, &config);
+                                stats.shields += 1;
+                                show_terrain(zone, &mut tilemaps, &config);
+                                log.add(format!(
+                                    "You put down a magic shield, it lasts {} turns or {} blocks",
+                                    config.shields.duration, config.shields.blocks
+                                ));
+                                Some(SoundHandler::new(&|s: &'s Sounds| &s.magic_sfx))
+                            }
+                            "magic" if player.magic < costs.shield_magic => {
+                                log.add(format!("A shield needs {} magic, you have {}", costs.shield_magic, player.magic));
+                                None
+                            }
+                            "magic" => {
+                                log.add("There is already a shield here");
+                                None
+                            }
+                            "power"
+                                if player.charisma >= power.charisma_needed
+                                    && player.magic >= power.magic_needed =>
+                            {
+                                aim.shape = Some(PowerShape::Line);
+                                log.add(aim_message(PowerShape::Line));
+                                None
+                            }
+                            "power" => {
+                                log.add(format!(
+                                    "Power needs {} charisma and {} magic, you have {} and {}",
+                                    power.charisma_needed, power.magic_needed, player.charisma, player.magic
+                                ));
+                                None
+                            }
+                            "pickup" => match remove_shield(zone, zone.cell) {
+                                Some(shield) => {
+                                    let refund = refund_shield(&shield, player, &config);
+                                    show_terrain(zone, &mut tilemaps, &config);
+                                    log.add(format!("You pick up the shield and get {:.1} magic back", refund));
+                                    Some(SoundHandler::new(&|s: &'s Sounds| &s.magic_sfx))
                                 }
-                                let sprite_sheet = &(h.unwrap());
-                                add_shield(
-                                    zone,
-                                    zone.cell,
-                                    &entities,
-                                    sprite_sheet,
-                                    &mut transforms,
-                                    &mut sprites,
-                                    &mut shields,
-                                    &config,
-                                );
+                                None => {
+                                    log.add("There is no shield here");
+                                    None
+                                }
+                            },
+                            "light" if !config.vision.enabled => None,
+                            "light" if player.magic >= costs.light_magic => {
+                                player.magic -= costs.light_magic;
+                                player.light = config.vision.light_moves;
+                                log.add(format!("A magic light lets you see further for {} moves", config.vision.light_moves));
                                 Some(SoundHandler::new(&|s: &'s Sounds| &s.magic_sfx))
                             }
-                            "power" if player.charisma > 9 && player.magic > 1 => {
-                                player.strength = player.strength.saturating_sub(5);
-                                player.charisma = player.charisma.saturating_sub(10);
-                                player.magic = player.magic.saturating_sub(1);
-                                let mut rng = rand::thread_rng();
-                                player.gold = player.gold.saturating_sub(rng.gen_range(0..10) + 15);
-                                power_clear(zone, zone.cell, &config);
-                                show_walls(zone, &cells, &mut tints);
-                                Some(SoundHandler::new(&|s: &'s Sounds| &s.power_sfx))
+                            "light" => {
+                                log.add(format!("Light needs {} magic, you have {}", costs.light_magic, player.magic));
+                                None
                             }
                             "restart" => {
                                 player.action = Some(Action::Restart);
@@ -122,27 +350,109 @@ impl<'s> System<'s> for ActionSystem {
                                 player.action = Some(Action::Help);
                                 None
                             }
-                            _ => None,
-                        };
-                        if let Some(h) = oh {
-                            play_sound(
-                                &*sounds,
-                                &storage,
-                                audio_output.as_ref().map(|o| o.deref()),
-                                &h.handle_func,
-                            );
-                        }
-                    } else {
-                        match action.as_ref() {
-                            // this is used as "resume" on help screen
-                            "restart" => {
-                                player.current_state = CurrentState::Gameplay;
+                            "log" => {
+                                log.expanded = !log.expanded;
+                                None
                             }
-                            _ => (),
-                        }
+                            "map" => {
+                                map_view.expanded = !map_view.expanded;
+                                None
+                            }
+                            "flask" if inventory.take(Item::WaterFlask) => {
+                                player.strength += config.items.flask_strength;
+                                log.add(format!(
+                                    "You drink from your water flask and get {} strength back",
+                                    config.items.flask_strength
+                                ));
+                                Some(SoundHandler::new(&|s: &'s Sounds| &s.fountain_sfx))
+                            }
+                            "scroll"
+                                if zone.revealed.contains(&zone.current)
+                                    && zone.target_known()
+                                    && inventory.count(Item::MapScroll) > 0 =>
+                            {
+                                log.add("This zone is already revealed, you keep your map scroll");
+                                None
+                            }
+                            "scroll" if inventory.take(Item::MapScroll) => {
+                                reveal_encounters(zone, &config);
+                                show_terrain(zone, &mut tilemaps, &config);
+                                log.add("The map scroll shows the fountains, merchants and gold of this zone");
+                                if let Some(message) = learn_clue(zone, ClueSource::MapScroll) {
+                                    log.add(message);
+                                }
+                                Some(SoundHandler::new(&|s: &'s Sounds| &s.magic_sfx))
+                            }
+                            "charm" if inventory.take(Item::Charm) => {
+                                effects.add(EffectKind::Frozen, config.effects.frozen, player.turn);
+                                log.add("Your charm keeps the inhabitants still, at no charisma cost");
+                                if !zone.inhabitants.is_empty() {
+                                    if let Some(message) = learn_clue(zone, ClueSource::Inhabitant) {
+                                        log.add(message);
+                                    }
+                                }
+                                Some(SoundHandler::new(&|s: &'s Sounds| &s.charisma_sfx))
+                            }
+                            "flask" => {
+                                log.add("You have no water flask, armourers sell them");
+                                None
+                            }
+                            "scroll" => {
+                                log.add("You have no map scroll, magicians sell them");
+                                None
+                            }
+                            "charm" => {
+                                log.add("You have no charm, armourers sell them");
+                                None
+                            }
+                            "spells" if player.spells.is_empty() => {
+                                log.add("You don't know any spells yet, magicians can teach you some");
+                                None
+                            }
+                            "spells" => {
+                                book.open = true;
+                                book.selected = book.selected.min(player.spells.len() - 1);
+                                None
+                            }
+                            _ => None,
+                        },
+                    }
+                };
+                if let Some(h) = oh {
+                    play_sound(
+                        &*sounds,
+                        &storage,
+                        audio_output.as_ref().map(|o| o.deref()),
+                        &h.handle_func,
+                    );
+                }
+            }
+            if pushed > 0 {
+                for zone in (&zones).join() {
+                    place_inhabitants(zone, &inhabitants, &mut transforms, &config);
+                }
+                for (entity, from) in inhabitant_positions {
+                    if let Some(transform) = transforms.get_mut(entity) {
+                        animate_move(entity, from, transform, &mut tweens, config.animation.move_duration);
                     }
                 }
             }
         }
     }
 }
+
+/// the direction chosen by a move action
+fn direction(action: &str) -> Option<(i32, i32)> {
+    match action {
+        "up" => Some((0, 1)),
+        "down" => Some((0, -1)),
+        "left" => Some((-1, 0)),
+        "right" => Some((1, 0)),
+        _ => None,
+    }
+}
+
+/// tell the player how to aim the power
+fn aim_message(shape: PowerShape) -> String {
+    format!("Power in a {}: choose a direction with the arrows, P for another shape", shape.name())
+}
@@ -0,0 +1,48 @@
+//! Animation system
+use amethyst::core::{math::Vector3, Time, Transform};
+use amethyst::ecs::{Entities, Entity, Join, Read, System, WriteStorage};
+
+use crate::components::{Tween, TweenKind};
+
+/// Move entities along their tweens, removing the tweens when done
+pub struct AnimationSystem;
+
+impl<'s> System<'s> for AnimationSystem {
+    type SystemData = (
+        Entities<'s>,
+        Read<'s, Time>,
+        WriteStorage<'s, Tween>,
+        WriteStorage<'s, Transform>,
+    );
+
+    fn run(&mut self, (entities, time, mut tweens, mut transforms): Self::SystemData) {
+        let mut done = vec![];
+        for (entity, tween, transform) in (&entities, &mut tweens, &mut transforms).join() {
+            tween.elapsed += time.delta_seconds();
+            transform.set_translation(tween.position());
+            if tween.is_done() {
+                done.push(entity);
+            }
+        }
+        for entity in done {
+            tweens.remove(entity);
+        }
+    }
+}
+
+/// animate an entity that has been moved, from its previous position to its new one
+pub fn animate_move<'s>(
+    entity: Entity,
+    from: Vector3<f32>,
+    transform: &mut Transform,
+    tweens: &mut WriteStorage<'s, Tween>,
+    duration: f32,
+) {
+    let to = *transform.translation();
+    if to != from {
+        transform.set_translation(from);
+        tweens
+            .insert(entity, Tween::new(TweenKind::Move, from, to, duration))
+            .unwrap();
+    }
+}
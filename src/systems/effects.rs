@@ -0,0 +1,28 @@
+//! Status effect system
+use amethyst::ecs::{Join, ReadStorage, System, Write, WriteStorage};
+
+use crate::components::{EffectKind, MessageLog, Player, StatusEffects};
+
+/// Count down the timed effects on the player as moves go by
+pub struct EffectSystem;
+
+impl<'s> System<'s> for EffectSystem {
+    type SystemData = (
+        ReadStorage<'s, Player>,
+        WriteStorage<'s, StatusEffects>,
+        Write<'s, MessageLog>,
+    );
+
+    fn run(&mut self, (players, mut effects, mut log): Self::SystemData) {
+        for (player, effects) in (&players, &mut effects).join() {
+            for kind in effects.tick(player.turn) {
+                log.add(match kind {
+                    EffectKind::Frozen => "The inhabitants move again",
+                    EffectKind::Haste => "Your haste wears off",
+                    EffectKind::HeatResistance => "You feel the heat again",
+                    EffectKind::Invisibility => "The inhabitants can see you again",
+                });
+            }
+        }
+    }
+}
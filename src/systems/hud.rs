@@ -0,0 +1,250 @@
+//! HUD system
+use amethyst::assets::AssetStorage;
+use amethyst::audio::{output::Output, Source};
+use amethyst::core::{Hidden, Time};
+use amethyst::ecs::{Entities, Entity, Join, Read, ReadExpect, ReadStorage, System, WriteStorage};
+use amethyst::ui::{UiImage, UiText, UiTransform};
+use std::ops::Deref;
+
+use crate::arrakis::{day_number, day_phase};
+use crate::audio::{play_sound, Sounds};
+use crate::components::{ArcanePower, CellType, GameMode, Inventory, Player, Run, ScreenScale, StatusEffects, Zone};
+use crate::config::{ArrakisConfig, CompassMode};
+
+/// the stats shown as bars, with their HUD ids
+const STATS: [&str; 4] = ["strength", "magic", "charisma", "gold"];
+
+/// Update the HUD built from the ui/hud.ron prefab with the player stats
+#[derive(Default)]
+pub struct HudSystem {
+    /// strength at the last update, to notice when it goes below a warning threshold
+    last_strength: Option<u32>,
+}
+
+impl<'s> System<'s> for HudSystem {
+    type SystemData = (
+        ReadStorage<'s, Player>,
+        ReadStorage<'s, Zone>,
+        ReadStorage<'s, StatusEffects>,
+        ReadStorage<'s, Inventory>,
+        Entities<'s>,
+        WriteStorage<'s, UiTransform>,
+        WriteStorage<'s, UiText>,
+        WriteStorage<'s, UiImage>,
+        WriteStorage<'s, Hidden>,
+        Read<'s, ArrakisConfig>,
+        Read<'s, ScreenScale>,
+        Read<'s, Run>,
+        Read<'s, Time>,
+        Read<'s, AssetStorage<Source>>,
+        Option<ReadExpect<'s, Sounds>>,
+        Option<Read<'s, Output>>,
+    );
+
+    fn run(
+        &mut self,
+        (players, zones, effects, inventories, entities, mut ui_transforms, mut ui_texts, mut ui_images, mut hiddens, config, scale,
+            run, time, storage, sounds, audio_output): Self::SystemData,
+    ) {
+        let (player, zone) = match (&players, &zones).join().next() {
+            Some(pz) => pz,
+            None => {
+                // no game running
+                self.last_strength = None;
+                return;
+            }
+        };
+        let hud = &config.hud;
+
+        // ui finder cannot be used while we write the transforms, so find the elements ourselves
+        let ids: Vec<(String, Entity)> = (&entities, &ui_transforms)
+            .join()
+            .map(|(entity, transform)| (transform.id.clone(), entity))
+            .collect();
+        let find = |id: &str| ids.iter().find(|(i, _)| i == id).map(|(_, e)| *e);
+
+        for stat in STATS.iter() {
+            let (value, max) = match *stat {
+                "strength" => (player.strength, hud.strength_max),
+                "magic" => (player.magic, hud.magic_max),
+                "charisma" => (player.charisma, hud.charisma_max),
+                _ => (player.gold, config.victory_gold),
+            };
+            if let Some(bar) = find(&format!("{}_bar", stat)).and_then(|e| ui_transforms.get_mut(e)) {
+                bar.width = if max == 0 { 1.0 } else { (value as f32 / max as f32).min(1.0) };
+            }
+            if let Some(text) = find(&format!("{}_value", stat)).and_then(|e| ui_texts.get_mut(e)) {
+                text.text = if *stat == "gold" {
+                    format!("{}/{}", value, max)
+                } else {
+                    value.to_string()
+                };
+            }
+        }
+
+        let encounter = match zone.current_type {
+            CellType::Fountain => "Fountain",
+            CellType::Armourer => "Armourer",
+            CellType::Magician => "Magician",
+            CellType::Oracle => "Oracle",
+            _ => zone.terrain_at(zone.cell).name(),
+        };
+        // the compass can't point to a hidden target
+        let show_compass = zone.target_known()
+            && (player.power == Some(ArcanePower::Farsight)
+                || match config.compass {
+                    CompassMode::Never => false,
+                    CompassMode::Oracle => player.compass,
+                    CompassMode::Always => true,
+                });
+        let (dx, dy) = zone.target_direction();
+        let compass = if !show_compass {
+            String::new()
+        } else if dx == 0 && dy == 0 {
+            "here".to_string()
+        } else {
+            let mut axes = vec![];
+            if dx != 0 {
+                axes.push(format!("{} {}", dx.abs(), if dx > 0 { "E" } else { "W" }));
+            }
+            if dy != 0 {
+                axes.push(format!("{} {}", dy.abs(), if dy > 0 { "N" } else { "S" }));
+            }
+            axes.join("\n")
+        };
+        let phase = day_phase(player.turn, &config);
+        let clock = phase.map_or(String::new(), |p| format!("Day {} {}", day_number(player.turn, &config), p.name));
+        if let Some(UiImage::SolidColor(color)) = find("day_tint").and_then(|e| ui_images.get_mut(e)) {
+            *color = phase.map_or([0., 0., 0., 0.], |p| p.tint);
+        }
+        let shield = zone
+            .shields
+            .iter()
+            .find(|s| s.cell == zone.cell)
+            .or_else(|| zone.shields.iter().min_by_key(|s| s.turns));
+        let shields = match shield {
+            Some(shield) => format!(
+                "{} {}/{}\n{} turns, {} blocks",
+                if shield.cell == zone.cell { "Shield here" } else { "Shields" },
+                zone.shields.len(),
+                config.shields.max_per_zone,
+                shield.turns,
+                shield.blocks
+            ),
+            None => String::new(),
+        };
+        let effects = (&players, &effects)
+            .join()
+            .next()
+            .map_or(String::new(), |(_, effects)| {
+                effects
+                    .effects
+                    .iter()
+                    .map(|e| format!("{} {}", e.kind.name(), e.turns))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            });
+        let inventory = (&players, &inventories)
+            .join()
+            .next()
+            .map_or(String::new(), |(_, inventory)| {
+                inventory
+                    .items
+                    .iter()
+                    .map(|(item, count)| match count {
+                        1 => item.label().to_string(),
+                        n => format!("{} x{}", item.label(), n),
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            });
+        let mode = match run.mode {
+            GameMode::Endless => format!("Endless, {} cleared", run.cleared),
+            GameMode::Classic if run.plus > 0 => match run.power {
+                Some(power) => format!("New Game+ {}, {}", run.plus, power.name()),
+                None => format!("New Game+ {}", run.plus),
+            },
+            GameMode::Classic => String::new(),
+            GameMode::Daily if run.ranked => format!("Daily {}", run.date),
+            GameMode::Daily => format!("Daily {}, practice", run.date),
+        };
+        let labels = [
+            ("mode", mode),
+            ("zone", format!("Zone {}", zone.current)),
+            ("target", match &zone.clues.heading {
+                // north-east shows as NE
+                Some(heading) if !zone.target_known() => format!(
+                    "Target {} {}",
+                    zone.target_name(),
+                    heading.split('-').filter_map(|w| w.chars().next()).collect::<String>().to_uppercase()
+                ),
+                _ => format!("Target {}", zone.target_name()),
+            }),
+            ("encounter", encounter.to_string()),
+            ("compass_text", compass),
+            ("clock", clock),
+            ("effects", effects),
+            ("inventory", inventory),
+            ("shields", shields),
+        ];
+        for (id, text) in labels.iter() {
+            if let Some(ui_text) = find(id).and_then(|e| ui_texts.get_mut(e)) {
+                ui_text.text = text.clone();
+            }
+        }
+
+        // labels follow the window size, like the other scaled texts
+        let label_ids = STATS
+            .iter()
+            .map(|stat| format!("{}_value", stat))
+            .chain(labels.iter().map(|(id, _)| id.to_string()));
+        for id in label_ids {
+            if let Some(text) = find(&id).and_then(|e| ui_texts.get_mut(e)) {
+                // the columns beside the minimap are narrow
+                let size = if id == "effects" || id == "inventory" || id == "mode" { hud.small_font_size } else { hud.font_size };
+                text.font_size = size * scale.0;
+            }
+        }
+
+        // the compass image is a 3x3 grid: arrows around, the target zone in the middle
+        if let Some(entity) = find("compass") {
+            if show_compass {
+                hiddens.remove(entity);
+            } else if !hiddens.contains(entity) {
+                hiddens.insert(entity, Hidden).unwrap();
+            }
+            if let Some(UiImage::PartialTexture { left, right, bottom, top, .. }) = ui_images.get_mut(entity) {
+                let col = (dx.signum() + 1) as f32;
+                let row = (1 - dy.signum()) as f32;
+                *left = col / 3.0;
+                *right = (col + 1.0) / 3.0;
+                *top = row / 3.0;
+                *bottom = (row + 1.0) / 3.0;
+            }
+        }
+
+        // pulse the strength bar while strength is low
+        let warning = hud.strength_warnings.iter().max().copied().unwrap_or(0);
+        if let Some(UiImage::SolidColor(color)) = find("strength_bar").and_then(|e| ui_images.get_mut(e)) {
+            color[3] = if player.strength < warning {
+                0.6 + 0.4 * (time.absolute_time_seconds() as f32 * 6.0).sin().abs()
+            } else {
+                1.0
+            };
+        }
+
+        // warn once each time strength goes below a threshold
+        if let Some(last) = self.last_strength {
+            let crossed = hud
+                .strength_warnings
+                .iter()
+                .any(|&w| last >= w && player.strength < w);
+            if crossed && hud.warning_sound {
+                if let Some(sounds) = sounds.as_ref() {
+                    play_sound(sounds.deref(), &storage, audio_output.as_ref().map(|o| o.deref()), &|s| &s.warning_sfx);
+                }
+            }
+        }
+        self.last_strength = Some(player.strength);
+    }
+}
@@ -0,0 +1,50 @@
+//! Message log system
+use amethyst::core::Hidden;
+use amethyst::ecs::{Entities, Join, Read, ReadStorage, System, WriteStorage};
+use amethyst::ui::{UiText, UiTransform};
+
+use crate::components::{LogHistory, MessageLog, ScreenScale};
+use crate::config::ArrakisConfig;
+
+/// Show the latest messages in the HUD log panel, and the full history when expanded
+pub struct LogSystem;
+
+impl<'s> System<'s> for LogSystem {
+    type SystemData = (
+        Entities<'s>,
+        ReadStorage<'s, UiTransform>,
+        WriteStorage<'s, UiText>,
+        ReadStorage<'s, LogHistory>,
+        WriteStorage<'s, Hidden>,
+        Read<'s, MessageLog>,
+        Read<'s, ArrakisConfig>,
+        Read<'s, ScreenScale>,
+    );
+
+    fn run(
+        &mut self,
+        (entities, ui_transforms, mut ui_texts, histories, mut hiddens, log, config, scale): Self::SystemData,
+    ) {
+        for (transform, text) in (&ui_transforms, &mut ui_texts).join() {
+            if transform.id == "log" {
+                // newest last, the panel is anchored at the bottom so it scrolls up
+                text.text = log.latest(config.log.lines).join("\n");
+                text.font_size = config.log.font_size * scale.0;
+            }
+        }
+        for (entity, _, text) in (&entities, &histories, &mut ui_texts).join() {
+            if log.expanded {
+                // newest first, older messages go off the bottom
+                let mut history = String::from("Messages (L to close)\n\n");
+                for message in log.messages.iter().rev() {
+                    history.push_str(message);
+                    history.push('\n');
+                }
+                text.text = history;
+                hiddens.remove(entity);
+            } else if !hiddens.contains(entity) {
+                hiddens.insert(entity, Hidden).unwrap();
+            }
+        }
+    }
+}
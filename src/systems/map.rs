@@ -0,0 +1,91 @@
+//! Map system
+use amethyst::core::Hidden;
+use amethyst::ecs::{Entities, Join, Read, ReadStorage, System, WriteStorage};
+use amethyst::ui::{UiImage, UiText};
+
+use crate::components::{CellType, MapCell, MapView, Player, WorldMap, Zone};
+
+const UNKNOWN: [f32; 4] = [0.1, 0.1, 0.1, 0.8];
+const VISITED: [f32; 4] = [0.35, 0.3, 0.25, 1.0];
+const CURRENT: [f32; 4] = [0.75, 0.65, 0.2, 1.0];
+const TARGET: [f32; 4] = [0.7, 0.15, 0.15, 1.0];
+
+/// Show the visited zones on the minimap and on the world map when it's opened
+pub struct MapSystem;
+
+impl<'s> System<'s> for MapSystem {
+    type SystemData = (
+        Entities<'s>,
+        ReadStorage<'s, Player>,
+        ReadStorage<'s, Zone>,
+        ReadStorage<'s, MapCell>,
+        ReadStorage<'s, WorldMap>,
+        WriteStorage<'s, UiText>,
+        WriteStorage<'s, UiImage>,
+        WriteStorage<'s, Hidden>,
+        Read<'s, MapView>,
+    );
+
+    fn run(
+        &mut self,
+        (entities, players, zones, cells, world_maps, mut ui_texts, mut ui_images, mut hiddens, view): Self::SystemData,
+    ) {
+        for entity in (&entities, &world_maps).join().map(|(entity, _)| entity) {
+            if view.expanded {
+                hiddens.remove(entity);
+            } else if !hiddens.contains(entity) {
+                hiddens.insert(entity, Hidden).unwrap();
+            }
+        }
+
+        for (_, zone) in (&players, &zones).join() {
+            for (cell, text, image) in (&cells, &mut ui_texts, &mut ui_images).join() {
+                // the world map isn't worth updating while it's hidden
+                if cell.world && !view.expanded {
+                    continue;
+                }
+                let number = cell.zone(zone.current);
+                let visited = zone.visited.contains(&number);
+                let color = if number == zone.current {
+                    CURRENT
+                } else if number == zone.target && zone.target_known() {
+                    TARGET
+                } else if visited {
+                    VISITED
+                } else {
+                    UNKNOWN
+                };
+                *image = UiImage::SolidColor(color);
+
+                let found = encounter_letters(zone, number);
+                text.text = if !cell.world {
+                    found
+                } else if visited || (number == zone.target && zone.target_known()) {
+                    format!("{}\n{}", number, found)
+                } else {
+                    String::new()
+                };
+            }
+        }
+    }
+}
+
+/// letters for the fountains and merchants found in a zone
+fn encounter_letters(zone: &Zone, number: i32) -> String {
+    let mut letters = String::new();
+    if let Some(found) = zone.discovered.get(&number) {
+        for (cell_type, letter) in [
+            (CellType::Fountain, 'F'),
+            (CellType::Armourer, 'A'),
+            (CellType::Magician, 'M'),
+            (CellType::Oracle, 'O'),
+        ]
+        .iter()
+        {
+            if found.values().any(|t| t == cell_type) {
+                letters.push(*letter);
+            }
+        }
+    }
+    letters
+}
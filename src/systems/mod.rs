@@ -1,8 +1,28 @@
 //! System module
 mod actions;
+mod animation;
+mod effects;
+mod hud;
+mod log;
+mod map;
 mod moves;
-mod status;
+mod preview;
+mod queue;
+mod resize;
+mod spellbook;
+mod stats;
+mod vision;
 
 pub use self::actions::ActionSystem;
+pub use self::animation::AnimationSystem;
+pub use self::effects::EffectSystem;
+pub use self::hud::HudSystem;
+pub use self::log::LogSystem;
+pub use self::map::MapSystem;
 pub use self::moves::MoveSystem;
-pub use self::status::StatusSystem;
+pub use self::preview::PreviewSystem;
+pub use self::queue::QueueSystem;
+pub use self::resize::ResizeSystem;
+pub use self::spellbook::SpellbookSystem;
+pub use self::stats::StatsSystem;
+pub use self::vision::VisionSystem;
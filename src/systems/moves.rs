@@ -2,155 +2,211 @@
 use amethyst::assets::AssetStorage;
 use amethyst::audio::{output::Output, Source};
 
-use amethyst::core::Transform;
-use amethyst::ecs::{Join, Read, ReadExpect, ReadStorage, System, WriteStorage,Entities, World};
-use amethyst::input::{InputEvent, StringBindings};
-use amethyst::renderer::{
-        resources::Tint,SpriteRender};
-use amethyst::shred::{DynamicSystemData};
-use amethyst::shrev::{EventChannel, ReaderId};
+use amethyst::core::{math::Vector3, Transform};
+use amethyst::ecs::{Join, Read, ReadExpect, ReadStorage, System, WriteStorage, Write, Entities, Entity};
+use amethyst::renderer::{Camera, SpriteRender};
+use amethyst::tiles::TileMap;
 use std::ops::Deref;
 
-use crate::arrakis::{perform_move, move_inhabitants, clear_shields, add_wizard, need_add_wizard};
+use super::animation::animate_move;
+use crate::arrakis::{perform_move, move_inhabitants, add_wizard, is_passable, need_add_wizard, run_rng, wizard_cell};
 use crate::audio::{play_wall_sound, play_sound, Sounds, SoundHandler};
-use crate::build::{build_zone, show_walls, place_inhabitants};
-use crate::components::{Cell, CellType, Player, Zone, Inhabitant, Action, CurrentState};
+use crate::build::{build_zone, show_terrain, place_inhabitants};
+use crate::components::{CellType, EffectKind, GameMode, Inventory, Run, RunStats, Player, StatusEffects, Zone, Inhabitant, ActionQueue, CurrentState, MessageLog, PowerAim, Spellbook, TerrainTile, Tween, TweenKind};
 use crate::config::ArrakisConfig;
 
-pub struct MoveSystem {
-    reader: Option<ReaderId<InputEvent<StringBindings>>>,
-}
-
-impl MoveSystem {
-    pub fn new() -> Self {
-        Self { reader: None }
-    }
-}
+pub struct MoveSystem;
 
 impl<'s> System<'s> for MoveSystem {
     type SystemData = (
         WriteStorage<'s, Transform>,
         WriteStorage<'s, Player>,
         WriteStorage<'s, Zone>,
-        ReadStorage<'s, Cell>,
-        WriteStorage<'s, Tint>,
+        WriteStorage<'s, StatusEffects>,
+        WriteStorage<'s, Inventory>,
+        WriteStorage<'s, RunStats>,
+        WriteStorage<'s, TileMap<TerrainTile>>,
         ReadStorage<'s,Inhabitant>,
+        ReadStorage<'s, Camera>,
         WriteStorage<'s, SpriteRender>,
+        WriteStorage<'s, Tween>,
         Entities<'s>,
-        Read<'s, EventChannel<InputEvent<StringBindings>>>,
+        Write<'s, ActionQueue>,
+        Read<'s, PowerAim>,
+        Read<'s, Spellbook>,
+        Read<'s, Run>,
+        Read<'s, CurrentState>,
+        Write<'s, MessageLog>,
         Read<'s, ArrakisConfig>,
         Read<'s, AssetStorage<Source>>,
         ReadExpect<'s, Sounds>,
         Option<Read<'s, Output>>,
     );
 
-    /// register event channel
-    fn setup(&mut self, w: &mut World) {
-        <Self::SystemData as DynamicSystemData>::setup(&self.accessor(), w);
-        self.reader = Some(
-            w.fetch_mut::<EventChannel<InputEvent<StringBindings>>>()
-                .register_reader(),
-        );
-    }
-
     fn run(
         &mut self,
-        (mut transforms, mut players, mut zones, cells, mut tints, inhabitants, 
-            mut sprites, entities, event, config, storage, sounds, 
+        (mut transforms, mut players, mut zones, mut effects, mut inventories, mut stats, mut tilemaps, inhabitants, cameras,
+            mut sprites, mut tweens, entities, mut queue, aim, book, run, state, mut log, config, storage, sounds, 
             audio_output): Self::SystemData,
     ) {
-        for event in event.read(self.reader.as_mut().unwrap()) {
-            if let InputEvent::ActionPressed(action) = event {
-                let mut should_move_inhabitants = false;
-                let mut should_place_inhabitants = false;
-                let mut should_add_wizard = false;
-                let mut has_moved = false;
-                let confr=&config.deref();
-                for (transform, player, zone) in (&mut transforms, &mut players, &mut zones).join(){
-                    if player.current_state ==  CurrentState::Gameplay {
-                    
-                        let (nz, nx, ny) = match action.as_ref() {
-                            "right" => move_right(zone, &config),
-                            "left" => move_left(zone, &config),
-                            "up" => move_up(zone, &config),
-                            "down" => move_down(zone, &config),
-                            _ => (zone.current, zone.cell.0, zone.cell.1),
-                        };
-                        // the action was a move
-                        if zone.cell.0 != nx || zone.cell.1 != ny || zone.current != nz {
-                            // zone change
-                            if zone.current != nz {
-                                zone.current = nz;
-                                zone.cell.0 = nx;
-                                zone.cell.1 = ny;
-                                clear_shields(zone, &entities);
-                                build_zone(zone, confr);
-                                // delete wizard if we leave target zone
-                                if zone.current != zone.target {
-                                    if let Some(wiz) = zone.wizard.take(){
-                                        entities.delete(entities.entity(wiz)).unwrap();
-                                    }
-                                }
-                                should_add_wizard=need_add_wizard(zone);
-                                show_walls(zone, &cells, &mut tints);
-                                perform_move(zone, transform, player, confr);
-                                should_place_inhabitants = true;
-                                has_moved = true;
-                                
-                            } else {
-                                // check we can move to the cell
-                                if zone.cells[nx][ny] < 2 {
-                                    zone.cell.0 = nx;
-                                    zone.cell.1 = ny;
-                                    perform_move(zone, transform, player, confr);
-                                    should_move_inhabitants = player.action != Some(Action::Charisma);
-                                    has_moved = true;
-                                } else {
-                                    play_wall_sound(&*sounds, &storage, audio_output.as_ref().map(|o| o.deref()));
-                                }
+        // no moves behind a menu, and wait for the previous move to be animated
+        if *state != CurrentState::Gameplay || (&tweens).join().next().is_some() {
+            return;
+        }
+        // the arrows choose the direction of the power being aimed, or the spell in the spellbook
+        if aim.shape.is_some() || book.open {
+            return;
+        }
+        if let Some(action) = queue.pop_move() {
+            let mut should_move_inhabitants = false;
+            let mut invisible = false;
+            let mut should_place_inhabitants = false;
+            let mut should_add_wizard = false;
+            let mut has_moved = false;
+            // where the camera comes from when changing zone
+            let mut slide_from = None;
+            let confr=&config.deref();
+            let zone_width = confr.arena.cell_count as f32 * confr.cell.width;
+            let zone_height = confr.arena.cell_count as f32 * confr.cell.height;
+            let inhabitant_positions: Vec<(Entity, Vector3<f32>)> = (&entities, &inhabitants, &transforms).join()
+                .map(|(entity, _, transform)| (entity, *transform.translation()))
+                .collect();
+            for (entity, transform, player, zone, effects, inventory, stats) in
+                (&entities, &mut transforms, &mut players, &mut zones, &mut effects, &mut inventories, &mut stats).join(){
+                let from = *transform.translation();
+                let (nz, nx, ny) = match action.as_ref() {
+                    "right" => move_right(zone, &config),
+                    "left" => move_left(zone, &config),
+                    "up" => move_up(zone, &config),
+                    "down" => move_down(zone, &config),
+                    _ => (zone.current, zone.cell.0, zone.cell.1),
+                };
+                // the action was a move
+                if zone.cell.0 != nx || zone.cell.1 != ny || zone.current != nz {
+                    // zone change
+                    if zone.current != nz {
+                        slide_from = Some(match nz - zone.current {
+                            10 => Vector3::new(-zone_width, 0.0, 0.0),
+                            -10 => Vector3::new(zone_width, 0.0, 0.0),
+                            100 => Vector3::new(0.0, -zone_height, 0.0),
+                            _ => Vector3::new(0.0, zone_height, 0.0),
+                        });
+                        zone.current = nz;
+                        zone.cell.0 = nx;
+                        zone.cell.1 = ny;
+                        build_zone(zone, confr, &run);
+                        // delete wizard if we leave target zone
+                        if zone.current != zone.target {
+                            if let Some(wiz) = zone.wizard.take(){
+                                entities.delete(entities.entity(wiz)).unwrap();
+                            }
+                        }
+                        should_add_wizard=need_add_wizard(zone);
+                        zone.visited.insert(zone.current);
+                        log.add(format!("You enter zone {}", zone.current));
+                        if zone.current == zone.target && !zone.target_known() {
+                            // stumbling upon the wizard tells where he lives
+                            zone.clues.row = true;
+                            zone.clues.column = true;
+                            log.add("You see the wizard's tower: this is his zone!");
+                        }
+                        perform_move(zone, transform, player, effects, inventory, stats, &run, confr, &mut log);
+                        stats.moves += 1;
+                        stats.encounter(zone.current_type);
+                        show_terrain(zone, &mut tilemaps, confr);
+                        should_place_inhabitants = true;
+                        has_moved = true;
+                        
+                    } else {
+                        // the wizard only lets you in with the gold, except in endless mode
+                        let refused = zone.current == zone.target && (nx, ny) == wizard_cell(confr)
+                            && run.mode != GameMode::Endless && player.gold < confr.victory_gold;
+                        // check we can move to the cell
+                        if zone.cells[nx][ny] < 2 && is_passable(zone, (nx, ny), confr) && !refused {
+                            zone.cell.0 = nx;
+                            zone.cell.1 = ny;
+                            perform_move(zone, transform, player, effects, inventory, stats, &run, confr, &mut log);
+                            stats.moves += 1;
+                            stats.encounter(zone.current_type);
+                            animate_move(entity, from, transform, &mut tweens, confr.animation.move_duration);
+                            // the new cell may be a discovery
+                            show_terrain(zone, &mut tilemaps, confr);
+                            should_move_inhabitants = !effects.has(EffectKind::Frozen);
+                            invisible = effects.has(EffectKind::Invisibility);
+                            has_moved = true;
+                        } else {
+                            let wall = Vector3::new(
+                                nx as f32 * confr.cell.width + confr.cell.width * 0.5,
+                                ny as f32 * confr.cell.height + confr.cell.height * 0.5,
+                                from.z);
+                            tweens.insert(entity, Tween::new(TweenKind::Bump, from, wall, confr.animation.bump_duration)).unwrap();
+                            if zone.cells[nx][ny] == 2 {
+                                stats.bumps += 1;
                             }
-                            // reset previous action
-                            player.action=None;
-                        } 
+                            play_wall_sound(&*sounds, &storage, audio_output.as_ref().map(|o| o.deref()));
+                            log.add(match zone.cells[nx][ny] {
+                                _ if refused => format!("The wizard sends you away: come back with {} gold, you have {}",
+                                    confr.victory_gold, player.gold),
+                                2 => "You bump into a wall".to_string(),
+                                18 => "An inhabitant is in the way".to_string(),
+                                _ => format!("You cannot cross the {}", zone.terrain_at((nx, ny)).name().to_lowercase()),
+                            });
+                        }
                     }
+                } 
+            }
+                
+            if should_place_inhabitants {
+                for (_, zone) in (&mut players, &mut zones).join(){
+                    place_inhabitants(zone, &inhabitants, &mut transforms, confr);
                 }
-                    
-                if should_place_inhabitants {
-                    for (_, zone) in (&mut players, &mut zones).join(){
-                        place_inhabitants(zone, &inhabitants, &mut transforms, confr);
-                    }
+            }
+            if should_move_inhabitants {
+                for (player, zone) in (&mut players, &mut zones).join(){
+                    let mut rng = run_rng(&run, zone.current, player.turn);
+                    move_inhabitants(zone, &inhabitants, &mut transforms, invisible, &mut rng, confr, &mut log);
+                    // shields may have worn out
+                    show_terrain(zone, &mut tilemaps, confr);
                 }
-                if should_move_inhabitants {
-                    for (_, zone) in (&mut players, &mut zones).join(){
-                        move_inhabitants(zone, &inhabitants, &mut transforms, confr);
+                for (entity, from) in inhabitant_positions {
+                    if let Some(transform) = transforms.get_mut(entity) {
+                        animate_move(entity, from, transform, &mut tweens, confr.animation.move_duration);
                     }
                 }
-                if should_add_wizard {
-                    // TODO there must be a better way
-                    let mut h = None;
-                    for sprite in (&mut sprites).join(){
-                        h = Some(sprite.sprite_sheet.clone());
-                        break;
-                    }
-                    let sprite_sheet=&(h.unwrap());
-                    for (_, zone) in (&mut players, &mut zones).join(){
-                        add_wizard(zone, &entities, sprite_sheet, &mut transforms, &mut sprites, confr);
-                    }
+            }
+            if let Some(offset) = slide_from {
+                // slide the new zone in from the direction of travel
+                for (entity, _, transform) in (&entities, &cameras, &mut transforms).join() {
+                    let rest = *transform.translation();
+                    tweens.insert(entity, Tween::new(TweenKind::Move, rest + offset, rest, confr.animation.zone_duration)).unwrap();
+                    transform.set_translation(rest + offset);
                 }
-                if has_moved {
-                   for (_, zone) in (&mut players, &mut zones).join(){
-                       let oh = match zone.current_type {
-                           CellType::Fountain => Some(SoundHandler::new(&|s: &'s Sounds| &s.fountain_sfx)),
-                           CellType::Armourer => Some(SoundHandler::new(&|s: &'s Sounds| &s.armourer_sfx)),
-                           CellType::Magician => Some(SoundHandler::new(&|s: &'s Sounds| &s.magician_sfx)),
-                           _ => None,
-                       };
-                       if let Some(h) = oh {
-                           play_sound(&*sounds, &storage, audio_output.as_ref().map(|o| o.deref()),&h.handle_func);
-                       }
-                   }
+            }
+            if should_add_wizard {
+                // TODO there must be a better way
+                let mut h = None;
+                for sprite in (&mut sprites).join(){
+                    h = Some(sprite.sprite_sheet.clone());
+                    break;
                 }
-
+                let sprite_sheet=&(h.unwrap());
+                for (_, zone) in (&mut players, &mut zones).join(){
+                    add_wizard(zone, &entities, sprite_sheet, &mut transforms, &mut sprites, confr);
+                }
+            }
+            if has_moved {
+               for (_, zone) in (&mut players, &mut zones).join(){
+                   let oh = match zone.current_type {
+                       CellType::Fountain => Some(SoundHandler::new(&|s: &'s Sounds| &s.fountain_sfx)),
+                       CellType::Armourer => Some(SoundHandler::new(&|s: &'s Sounds| &s.armourer_sfx)),
+                       CellType::Magician => Some(SoundHandler::new(&|s: &'s Sounds| &s.magician_sfx)),
+                       CellType::Oracle => Some(SoundHandler::new(&|s: &'s Sounds| &s.magic_sfx)),
+                       _ => None,
+                   };
+                   if let Some(h) = oh {
+                       play_sound(&*sounds, &storage, audio_output.as_ref().map(|o| o.deref()),&h.handle_func);
+                   }
+               }
             }
         }
     }
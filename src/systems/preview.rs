@@ -0,0 +1,72 @@
+//! Shield preview system
+use amethyst::core::{Hidden, Transform};
+use amethyst::ecs::{Entities, Join, Read, ReadStorage, System, WriteStorage};
+use amethyst::input::{InputHandler, StringBindings};
+use amethyst::renderer::SpriteRender;
+
+use crate::arrakis::shield_preview;
+use crate::build::encounter_sprite;
+use crate::components::{CurrentState, Player, PowerAim, ShieldPreview, Spellbook, Zone};
+use crate::config::ArrakisConfig;
+
+/// While the magic key is held, show the encounters a shield put down here would make
+pub struct PreviewSystem;
+
+impl<'s> System<'s> for PreviewSystem {
+    type SystemData = (
+        Entities<'s>,
+        ReadStorage<'s, Player>,
+        ReadStorage<'s, Zone>,
+        ReadStorage<'s, ShieldPreview>,
+        WriteStorage<'s, Transform>,
+        WriteStorage<'s, SpriteRender>,
+        WriteStorage<'s, Hidden>,
+        Read<'s, InputHandler<StringBindings>>,
+        Read<'s, CurrentState>,
+        Read<'s, PowerAim>,
+        Read<'s, Spellbook>,
+        Read<'s, ArrakisConfig>,
+    );
+
+    fn run(
+        &mut self,
+        (entities, players, zones, previews, mut transforms, mut sprites, mut hiddens, input, state, aim, book, config): Self::SystemData,
+    ) {
+        let held = config.shield_preview
+            && *state == CurrentState::Gameplay
+            && aim.shape.is_none()
+            && !book.open
+            && input.action_is_down("magic") == Some(true);
+        let mut preview = vec![];
+        if held {
+            for (_, zone) in (&players, &zones).join() {
+                // no preview where a shield can't go
+                if zone.cells[zone.cell.0][zone.cell.1] == 0 {
+                    preview = shield_preview(zone, &config);
+                }
+            }
+        }
+
+        let mut cells = preview
+            .into_iter()
+            .filter_map(|(pos, cell_type)| encounter_sprite(cell_type).map(|sprite| (pos, sprite)));
+        for (entity, _, transform, sprite) in (&entities, &previews, &mut transforms, &mut sprites).join() {
+            match cells.next() {
+                Some(((x, y), number)) => {
+                    transform.set_translation_xyz(
+                        x as f32 * config.cell.width + config.cell.width * 0.5,
+                        y as f32 * config.cell.height + config.cell.height * 0.5,
+                        0.5,
+                    );
+                    sprite.sprite_number = number;
+                    hiddens.remove(entity);
+                }
+                None => {
+                    if !hiddens.contains(entity) {
+                        hiddens.insert(entity, Hidden).unwrap();
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,55 @@
+//! Input queue system
+use amethyst::ecs::{Read, System, World, Write};
+use amethyst::input::{InputEvent, StringBindings};
+use amethyst::shred::DynamicSystemData;
+use amethyst::shrev::{EventChannel, ReaderId};
+
+use crate::components::{ActionQueue, CurrentState};
+use crate::config::ArrakisConfig;
+
+/// Queue the actions pressed during gameplay, so they're handled once the current animation is done
+pub struct QueueSystem {
+    reader: Option<ReaderId<InputEvent<StringBindings>>>,
+}
+
+impl QueueSystem {
+    pub fn new() -> Self {
+        Self { reader: None }
+    }
+}
+
+impl<'s> System<'s> for QueueSystem {
+    type SystemData = (
+        Read<'s, CurrentState>,
+        Read<'s, ArrakisConfig>,
+        Read<'s, EventChannel<InputEvent<StringBindings>>>,
+        Write<'s, ActionQueue>,
+    );
+
+    /// register event channel
+    fn setup(&mut self, w: &mut World) {
+        <Self::SystemData as DynamicSystemData>::setup(&self.accessor(), w);
+        self.reader = Some(
+            w.fetch_mut::<EventChannel<InputEvent<StringBindings>>>()
+                .register_reader(),
+        );
+    }
+
+    fn run(&mut self, (state, config, event, mut queue): Self::SystemData) {
+        for event in event.read(self.reader.as_mut().unwrap()) {
+            // input is still read while a menu is shown, but ignored
+            if *state != CurrentState::Gameplay {
+                continue;
+            }
+            match event {
+                // with the preview, the shield is shown while the key is held and put down on release
+                InputEvent::ActionPressed(action) if config.shield_preview && action == "magic" => {}
+                InputEvent::ActionReleased(action) if config.shield_preview && action == "magic" => {
+                    queue.actions.push_back(action.clone());
+                }
+                InputEvent::ActionPressed(action) => queue.actions.push_back(action.clone()),
+                _ => {}
+            }
+        }
+    }
+}
@@ -0,0 +1,112 @@
+//! Resize system
+use amethyst::ecs::hibitset::BitSet;
+use amethyst::ecs::storage::ComponentEvent;
+use amethyst::ecs::{Entities, Entity, Join, Read, ReadExpect, ReadStorage, System, World, WorldExt, Write, WriteStorage};
+use amethyst::renderer::Camera;
+use amethyst::shred::DynamicSystemData;
+use amethyst::shrev::ReaderId;
+use amethyst::ui::{UiText, UiTransform};
+use amethyst::window::ScreenDimensions;
+
+use crate::components::{ScaledUi, ScreenScale};
+use crate::config::ArrakisConfig;
+
+/// Fit the camera and the UI to the window, preserving the aspect ratio of the logical screen:
+/// the extra space on the sides or at the top and bottom stays black
+pub struct ResizeSystem {
+    /// window size at the last fit
+    size: Option<(f32, f32)>,
+    /// cameras fitted to that size
+    cameras: Vec<Entity>,
+    /// tells about the UI elements created since the last frame
+    reader: Option<ReaderId<ComponentEvent>>,
+    /// UI elements created since the last frame
+    added: BitSet,
+}
+
+impl ResizeSystem {
+    pub fn new() -> Self {
+        Self {
+            size: None,
+            cameras: vec![],
+            reader: None,
+            added: BitSet::new(),
+        }
+    }
+}
+
+impl<'s> System<'s> for ResizeSystem {
+    type SystemData = (
+        Entities<'s>,
+        ReadExpect<'s, ScreenDimensions>,
+        Read<'s, ArrakisConfig>,
+        WriteStorage<'s, Camera>,
+        ReadStorage<'s, ScaledUi>,
+        WriteStorage<'s, UiTransform>,
+        WriteStorage<'s, UiText>,
+        Write<'s, ScreenScale>,
+    );
+
+    /// register for the UI elements created
+    fn setup(&mut self, w: &mut World) {
+        <Self::SystemData as DynamicSystemData>::setup(&self.accessor(), w);
+        self.reader = Some(w.write_storage::<ScaledUi>().register_reader());
+    }
+
+    /// everything is fitted when the window changes, otherwise only the entities created since the last frame
+    fn run(
+        &mut self,
+        (entities, dimensions, config, mut cameras, scaled_uis, mut ui_transforms, mut ui_texts, mut screen_scale): Self::SystemData,
+    ) {
+        let size = (dimensions.width(), dimensions.height());
+        let resized = self.size != Some(size);
+        self.size = Some(size);
+        let scale = fit_scale(&dimensions, &config);
+        screen_scale.0 = scale;
+
+        let mut new_camera = false;
+        for (entity, camera) in (&entities, &mut cameras).join() {
+            if resized || !self.cameras.contains(&entity) {
+                *camera = Camera::standard_2d(dimensions.width() / scale, dimensions.height() / scale);
+                new_camera = true;
+            }
+        }
+        if new_camera {
+            self.cameras = (&entities, &cameras).join().map(|(entity, _)| entity).collect();
+        }
+
+        self.added.clear();
+        for event in scaled_uis.channel().read(self.reader.as_mut().unwrap()) {
+            if let ComponentEvent::Inserted(id) | ComponentEvent::Modified(id) = event {
+                self.added.add(*id);
+            }
+        }
+        if resized {
+            for (scaled, transform, text) in (&scaled_uis, &mut ui_transforms, (&mut ui_texts).maybe()).join() {
+                fit_ui(scaled, transform, text, scale, &config);
+            }
+        } else {
+            for (scaled, transform, text, _) in
+                (&scaled_uis, &mut ui_transforms, (&mut ui_texts).maybe(), &self.added).join()
+            {
+                fit_ui(scaled, transform, text, scale, &config);
+            }
+        }
+    }
+}
+
+/// ratio between window pixels and logical screen units
+fn fit_scale(dimensions: &ScreenDimensions, config: &ArrakisConfig) -> f32 {
+    (dimensions.width() / config.screen.width).min(dimensions.height() / config.screen.height)
+}
+
+/// place and size a UI element for the given scale
+fn fit_ui(scaled: &ScaledUi, transform: &mut UiTransform, text: Option<&mut UiText>, scale: f32, config: &ArrakisConfig) {
+    transform.local_x = (scaled.x - config.screen.width * 0.5) * scale;
+    transform.local_y = (scaled.y - config.screen.height * 0.5) * scale;
+    transform.width = scaled.width * scale;
+    transform.height = scaled.height * scale;
+    if let Some(text) = text {
+        text.font_size = scaled.font_size * scale;
+    }
+}
@@ -0,0 +1,51 @@
+//! Spellbook system
+use amethyst::core::Hidden;
+use amethyst::ecs::{Entities, Join, Read, ReadStorage, System, WriteStorage};
+use amethyst::ui::UiText;
+
+use crate::components::{Player, Spellbook, SpellbookPanel};
+use crate::config::ArrakisConfig;
+
+/// Show the spellbook panel with the spells learned, their costs and the selected one
+pub struct SpellbookSystem;
+
+impl<'s> System<'s> for SpellbookSystem {
+    type SystemData = (
+        Entities<'s>,
+        ReadStorage<'s, Player>,
+        ReadStorage<'s, SpellbookPanel>,
+        WriteStorage<'s, UiText>,
+        WriteStorage<'s, Hidden>,
+        Read<'s, Spellbook>,
+        Read<'s, ArrakisConfig>,
+    );
+
+    fn run(&mut self, (entities, players, panels, mut ui_texts, mut hiddens, book, config): Self::SystemData) {
+        for (entity, _, text) in (&entities, &panels, &mut ui_texts).join() {
+            if !book.open {
+                if !hiddens.contains(entity) {
+                    hiddens.insert(entity, Hidden).unwrap();
+                }
+                continue;
+            }
+            let mut page = String::from("Spellbook\n\n");
+            if book.blinking {
+                page.push_str("Blink: choose a direction with the arrows, B to give up\n");
+            } else {
+                for player in (&players).join() {
+                    for (i, spell) in player.spells.iter().enumerate() {
+                        page.push_str(&format!(
+                            "{} {}: {} magic\n",
+                            if i == book.selected { ">" } else { " " },
+                            spell.name(),
+                            config.spells.magic(*spell)
+                        ));
+                    }
+                }
+                page.push_str("\nArrows to choose, Enter to cast, B to close\n");
+            }
+            text.text = page;
+            hiddens.remove(entity);
+        }
+    }
+}
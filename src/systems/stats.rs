@@ -0,0 +1,26 @@
+//! Run statistics system
+use amethyst::core::Time;
+use amethyst::ecs::{Join, Read, ReadStorage, System, WriteStorage};
+
+use crate::components::{CurrentState, RunStats, Zone};
+
+/// Keep the run stats that aren't counted where they happen: zones visited and play time
+pub struct StatsSystem;
+
+impl<'s> System<'s> for StatsSystem {
+    type SystemData = (
+        ReadStorage<'s, Zone>,
+        WriteStorage<'s, RunStats>,
+        Read<'s, CurrentState>,
+        Read<'s, Time>,
+    );
+
+    fn run(&mut self, (zones, mut stats, state, time): Self::SystemData) {
+        for (zone, stats) in (&zones, &mut stats).join() {
+            stats.zones = zone.visited.len() as u32;
+            if *state == CurrentState::Gameplay {
+                stats.play_time += time.delta_seconds();
+            }
+        }
+    }
+}
@@ -0,0 +1,85 @@
+//! Vision system
+use amethyst::core::{math::Point3, Hidden};
+use amethyst::ecs::{Entities, Entity, Join, Read, ReadStorage, System, WriteStorage};
+use amethyst::renderer::{palette::Srgba, resources::Tint};
+use amethyst::tiles::TileMap;
+
+use crate::arrakis::{visible_cells, vision_radius, wizard_cell};
+use crate::components::{Inhabitant, Inventory, Player, TerrainTile, Zone};
+use crate::config::ArrakisConfig;
+
+/// In limited vision mode, dim the terrain out of sight of the player and hide the inhabitants there
+#[derive(Default)]
+pub struct VisionSystem {
+    /// cells visible at the last update, to only touch the tiles when it changes
+    last: Vec<Vec<bool>>,
+    /// the tile map dimmed at the last update: a new game or a restart makes a new one, with every tile lit
+    tilemap: Option<Entity>,
+}
+
+impl<'s> System<'s> for VisionSystem {
+    type SystemData = (
+        Entities<'s>,
+        ReadStorage<'s, Player>,
+        ReadStorage<'s, Zone>,
+        ReadStorage<'s, Inventory>,
+        ReadStorage<'s, Inhabitant>,
+        WriteStorage<'s, TileMap<TerrainTile>>,
+        WriteStorage<'s, Hidden>,
+        WriteStorage<'s, Tint>,
+        Read<'s, ArrakisConfig>,
+    );
+
+    fn run(
+        &mut self,
+        (entities, players, zones, inventories, inhabitants, mut tilemaps, mut hiddens, mut tints, config): Self::SystemData,
+    ) {
+        if !config.vision.enabled {
+            return;
+        }
+        let tilemap = (&entities, &tilemaps).join().next().map(|(entity, _)| entity);
+        if tilemap != self.tilemap {
+            self.tilemap = tilemap;
+            self.last.clear();
+        }
+        for (player, zone, inventory) in (&players, &zones, &inventories).join() {
+            let visible = visible_cells(zone, vision_radius(player, inventory, &config), &config);
+
+            // inhabitants move every turn, and can be recreated with a new game
+            for (&(x, y), (entity, _)) in zone.inhabitants.iter().zip((&entities, &inhabitants).join()) {
+                if visible[x][y] {
+                    hiddens.remove(entity);
+                } else if !hiddens.contains(entity) {
+                    hiddens.insert(entity, Hidden).unwrap();
+                }
+            }
+            // the wizard doesn't move: show where it is, dimmed
+            if let Some(wizard) = zone.wizard {
+                let (wx, wy) = wizard_cell(&config);
+                let tint = if visible[wx][wy] {
+                    Tint(Srgba::new(1.0, 1.0, 1.0, 1.0))
+                } else {
+                    Tint(Srgba::new(0.25, 0.25, 0.3, 1.0))
+                };
+                tints.insert(entities.entity(wizard), tint).unwrap();
+            }
+
+            if visible == self.last {
+                continue;
+            }
+            for tilemap in (&mut tilemaps).join() {
+                let count = visible.len();
+                for x in 0..count {
+                    for y in 0..count {
+                        // tile rows go down from the top
+                        let coord = Point3::new(x as u32, (count - 1 - y) as u32, 0);
+                        if let Some(tile) = tilemap.get_mut(&coord) {
+                            tile.dim = !visible[x][y];
+                        }
+                    }
+                }
+            }
+            self.last = visible;
+        }
+    }
+}
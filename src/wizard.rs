@@ -0,0 +1,211 @@
+//! Wizard encounter, the end of the game
+use amethyst::{
+    ecs::{Entity, Join},
+    input::{is_close_requested, is_key_down, VirtualKeyCode},
+    prelude::*,
+    ui::{Anchor, LineMode, UiImage, UiText},
+};
+
+use crate::build::{load_font, scaled_ui};
+use crate::components::{ArcanePower, Player, Run, RunStats, Spell, Zone};
+use crate::config::ArrakisConfig;
+use crate::menu::{Menu, MenuEntry, MenuItem};
+use crate::save::{LifetimeStats, SaveGame, SaveSlot};
+use crate::scores::{daily_result, is_ranked, score, HighScores, Score};
+use crate::states::InterTitle;
+
+/// Dialogue with the wizard, pushed over the game when the player reaches him with the gold:
+/// pay him, choose the power to learn, and see how the story ends
+pub struct WizardEncounter {
+    menu: Menu,
+    /// the wizard's words, above the menu
+    dialogue: Option<Entity>,
+    /// once paid, the player has to choose a power
+    paid: bool,
+}
+
+impl WizardEncounter {
+    pub fn new(config: &ArrakisConfig) -> WizardEncounter {
+        WizardEncounter {
+            menu: Menu::new(
+                "The Wizard of Arrakis",
+                vec![
+                    MenuEntry::new(MenuItem::Pay, &format!("Pay {} gold", config.victory_gold)),
+                    MenuEntry::new(MenuItem::Leave, "Leave"),
+                ],
+            ),
+            dialogue: None,
+            paid: false,
+        }
+    }
+
+    /// show what the wizard says
+    fn say(&mut self, world: &mut World, words: String) {
+        if let Some(e) = self.dialogue {
+            if let Some(text) = world.write_storage::<UiText>().get_mut(e) {
+                text.text = words;
+            }
+            return;
+        }
+        let font = load_font(world);
+        let (mut transform, scaled, font_size) = {
+            let config = world.read_resource::<ArrakisConfig>();
+            let font_size = config.status.font_size * 0.6;
+            let (transform, scaled) = scaled_ui(
+                "wizard_dialogue",
+                config.screen.width * 0.5,
+                config.screen.height * 0.87,
+                config.screen.width * 0.9,
+                config.screen.height * 0.24,
+                font_size,
+            );
+            (transform, scaled, font_size)
+        };
+        // above the HUD, like the menu
+        transform.local_z = 2.;
+        let text = UiText::new(font, words, [1., 0.95, 0.8, 1.], font_size, LineMode::Wrap, Anchor::Middle);
+        self.dialogue = Some(
+            world
+                .create_entity()
+                .with(transform)
+                .with(scaled)
+                .with(text)
+                .with(UiImage::SolidColor([0.1, 0.05, 0.15, 0.9]))
+                .build(),
+        );
+    }
+}
+
+impl SimpleState for WizardEncounter {
+    fn on_start(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        let world = data.world;
+        let (gold, price) = {
+            let config = world.read_resource::<ArrakisConfig>();
+            let gold = (&world.read_storage::<Player>()).join().next().map_or(0, |p| p.gold);
+            (gold, config.victory_gold)
+        };
+        self.menu.show(world);
+        self.say(
+            world,
+            format!(
+                "You stand before the wizard with {} gold.\n\"For {} gold, I will teach you one of my arcane powers.\"",
+                gold, price
+            ),
+        );
+    }
+
+    fn on_stop(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        self.menu.hide(data.world);
+        if let Some(e) = self.dialogue.take() {
+            data.world.delete_entity(e).unwrap();
+        }
+    }
+
+    fn handle_event(&mut self, data: StateData<'_, GameData<'_, '_>>, event: StateEvent) -> SimpleTrans {
+        let world = data.world;
+        if let StateEvent::Window(event) = &event {
+            if is_close_requested(&event) {
+                return Trans::Quit;
+            }
+            if !self.paid && is_key_down(&event, VirtualKeyCode::Escape) {
+                return Trans::Pop;
+            }
+        }
+        match self.menu.handle_event(world, &event) {
+            Some((MenuItem::Leave, _)) => Trans::Pop,
+            Some((MenuItem::Pay, _)) => {
+                self.paid = true;
+                let price = world.read_resource::<ArrakisConfig>().victory_gold;
+                for (player, stats) in (&mut world.write_storage::<Player>(), &mut world.write_storage::<RunStats>()).join() {
+                    stats.spend(price.min(player.gold));
+                    player.gold = player.gold.saturating_sub(price);
+                }
+                self.menu.hide(world);
+                self.menu = Menu::new(
+                    "Choose an arcane power",
+                    ArcanePower::ALL
+                        .iter()
+                        .map(|power| MenuEntry::new(MenuItem::Learn(*power), power.name()))
+                        .collect(),
+                );
+                self.menu.show(world);
+                let mut words = String::from("The wizard takes your gold. \"Choose well:\"\n");
+                for power in ArcanePower::ALL.iter() {
+                    words.push_str(&format!("{}: {}\n", power.name(), power.description()));
+                }
+                self.say(world, words);
+                Trans::None
+            }
+            Some((MenuItem::Learn(power), _)) => {
+                let config = world.read_resource::<ArrakisConfig>().clone();
+                let run = world.read_resource::<Run>().clone();
+                let stats = (&world.read_storage::<RunStats>()).join().next().cloned().unwrap_or_default();
+                LifetimeStats::record(&stats, true);
+                // a finished run can't be continued
+                SaveGame::delete(SaveSlot::Auto);
+                let mut text = String::new();
+                let mut rank = None;
+                for (player, zone) in (&mut world.write_storage::<Player>(), &world.read_storage::<Zone>()).join() {
+                    player.power = Some(power);
+                    if power == ArcanePower::Spellmaster {
+                        player.spells = Spell::ALL.to_vec();
+                    }
+                    let points = score(player, zone, &run, true, &config);
+                    // the daily result is kept below, which ends the ranked attempt
+                    if is_ranked(&run) {
+                        rank = HighScores::record(Score::new(points, &run, &config), config.score.table_size);
+                    }
+                    let daily = daily_result(&run, player, zone, true);
+                    text = ending(player, power, &stats, points, rank, &daily, &config);
+                }
+                let plus = run.new_game_plus(power);
+                // the high scores first, the ending is shown when they're closed
+                Trans::Sequence(vec![
+                    Trans::Pop,
+                    Trans::Switch(Box::new(InterTitle::ending(text, &run, plus))),
+                    Trans::Push(Box::new(InterTitle::high_scores(&config, rank.map(|r| (run.mode, r))))),
+                ])
+            }
+            _ => Trans::None,
+        }
+    }
+}
+
+/// the end of the story, depending on the power learned and what's left of the player, and the stats of the run
+fn ending(
+    player: &Player,
+    power: ArcanePower,
+    stats: &RunStats,
+    points: u32,
+    rank: Option<usize>,
+    daily: &str,
+    config: &ArrakisConfig,
+) -> String {
+    let weak = config.hud.strength_warnings.iter().max().copied().unwrap_or(0);
+    let epilogue = if player.strength < weak {
+        "Exhausted by the journey, you fall asleep at the wizard's feet. Your training starts when you wake up."
+    } else if player.gold >= config.victory_gold / 4 {
+        "With gold still in your purse, you buy a house by the tower and study in comfort."
+    } else if player.charisma >= config.hud.charisma_max {
+        "The wizard enjoys your company so much that he makes you his heir."
+    } else if player.magic >= config.hud.magic_max {
+        "Your magic already impresses the wizard: you will be his equal in no time."
+    } else {
+        "You leave the tower at dusk, ready for new journeys."
+    };
+    let mut score = match rank {
+        Some(rank) => format!("Score {}, #{} in the high scores", points, rank),
+        None => format!("Score {}", points),
+    };
+    if !daily.is_empty() {
+        score = format!("{}\n{}", score, daily);
+    }
+    format!(
+        "You WIN!\n\nYou are now a {}: {}.\n{}\n\n{}\n{}\n\nPress R to have another go, N for New Game+ with your power",
+        power.name(),
+        power.description(),
+        epilogue,
+        score,
+        stats.summary()
+    )
+}